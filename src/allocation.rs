@@ -0,0 +1,197 @@
+/*
+Utility slot allocation.
+
+Shield boosters compete for utility slots with point defence, chaff, heat
+sinks, ECM and the like.  Given the number of utility slots and the other
+utilities wanted, this finds the best loadout for each number of boosters
+that fits, and how many boosters to fit.
+
+Other utilities are either required, and always take a slot, or optional with
+a value weight: the seconds of shield survival time the utility is worth.
+Slots not given to boosters go to the most valuable optional utilities, and
+the recommended booster count is the one with the highest survival time plus
+the value of the utilities it leaves room for.
+*/
+
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::{optimise, Database, Error, Scenario, TestResult};
+
+/// Another utility wanting a slot, written as `<name>` if required or
+/// `<name>,<weight>` if optional, for example `Point Defence` or `Heat Sink,20`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UtilitySpec {
+    pub name: String,
+    /// Seconds of survival time the utility is worth, if it's optional
+    pub weight: Option<f64>,
+}
+
+impl FromStr for UtilitySpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::InvalidUtility(s.to_string());
+        let mut parts = s.split(',').map(str::trim);
+
+        let name = parts.next().filter(|p| !p.is_empty()).ok_or_else(err)?;
+        let weight = match parts.next() {
+            Some(weight) => Some(
+                weight
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|w| w.is_finite())
+                    .ok_or_else(err)?,
+            ),
+            None => None,
+        };
+
+        if parts.next().is_some() {
+            return Err(err());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            weight,
+        })
+    }
+}
+
+/// The best loadout with a given number of boosters, and what else fits.
+#[derive(Debug, Clone, Serialize)]
+pub struct Allocation {
+    pub boosters: usize,
+    /// None if no loadout with this many boosters fits the power budget and
+    /// constraints
+    pub result: Option<TestResult>,
+    /// Survival time gained over one fewer booster
+    pub marginal: Option<f64>,
+    /// Optional utilities fitted in the remaining slots
+    pub utilities: Vec<UtilitySpec>,
+    /// Survival time plus the weight of the optional utilities fitted
+    pub value: f64,
+}
+
+impl Allocation {
+    fn utility_value(&self) -> f64 {
+        self.utilities.iter().filter_map(|u| u.weight).sum()
+    }
+}
+
+/// Survival time, or infinity if the shield never falls.
+fn survival(result: &TestResult) -> f64 {
+    if result.survival_time < 0.0 {
+        f64::INFINITY
+    } else {
+        result.survival_time
+    }
+}
+
+/// Try every number of boosters which fits alongside the required utilities.
+///
+/// Any mix of booster ratings in the scenario is replaced by its single rating.
+pub fn allocate(
+    scenario: &Scenario,
+    db: &Database,
+    slots: usize,
+    utilities: &[UtilitySpec],
+) -> Result<Vec<Allocation>, Error> {
+    let required = utilities.iter().filter(|u| u.weight.is_none()).count();
+    if required > slots {
+        return Err(Error::TooManyUtilities { required, slots });
+    }
+
+    let mut optional: Vec<&UtilitySpec> = utilities.iter().filter(|u| u.weight.is_some()).collect();
+    optional.sort_by(|a, b| b.weight.partial_cmp(&a.weight).unwrap());
+
+    let mut allocations: Vec<Allocation> = vec![];
+
+    for boosters in 0..=(slots - required).min(8) {
+        let scenario = Scenario {
+            shield_booster_count: boosters,
+            booster_ratings: vec![],
+            top: 1,
+            ..scenario.clone()
+        };
+
+        let result = match optimise(&scenario, db) {
+            Ok(mut outcome) if !outcome.results.is_empty() => Some(outcome.results.remove(0)),
+            Ok(_)
+            | Err(Error::InsufficientPower { .. })
+            | Err(Error::Infeasible(_))
+            | Err(Error::TooManyPinnedBoosters { .. }) => None,
+            Err(e) => return Err(e),
+        };
+
+        let marginal = match (allocations.last().and_then(|a| a.result.as_ref()), &result) {
+            // Nothing to gain once the shield never falls
+            (Some(last), Some(_)) if survival(last).is_infinite() => Some(0.0),
+            (Some(last), Some(result)) => Some(survival(result) - survival(last)),
+            _ => None,
+        };
+
+        let utilities: Vec<UtilitySpec> = optional
+            .iter()
+            .take(slots - required - boosters)
+            .map(|&u| u.clone())
+            .collect();
+        let fitted: f64 = utilities.iter().filter_map(|u| u.weight).sum();
+
+        allocations.push(Allocation {
+            boosters,
+            value: result.as_ref().map_or(f64::NEG_INFINITY, survival) + fitted,
+            result,
+            marginal,
+            utilities,
+        });
+    }
+
+    Ok(allocations)
+}
+
+/// The allocation with the most value.  Where the shield never falls either
+/// way, prefer more valuable utilities, then fewer boosters.
+pub fn recommend(allocations: &[Allocation]) -> Option<&Allocation> {
+    allocations
+        .iter()
+        .filter(|a| a.result.is_some())
+        .max_by(|a, b| {
+            a.value
+                .partial_cmp(&b.value)
+                .unwrap()
+                .then_with(|| a.utility_value().partial_cmp(&b.utility_value()).unwrap())
+                .then_with(|| b.boosters.cmp(&a.boosters))
+        })
+}
+
+#[test]
+fn test_allocate() {
+    let db = Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        kinetic_dps: 100.0,
+        thermal_dps: 50.0,
+        ..Scenario::default()
+    };
+    let utilities: Vec<UtilitySpec> = vec![
+        "Point Defence".parse().unwrap(),
+        "Heat Sink, 1000".parse().unwrap(),
+        "Chaff Launcher,0.5".parse().unwrap(),
+    ];
+
+    let allocations = allocate(&scenario, &db, 4, &utilities).unwrap();
+    assert_eq!(allocations.len(), 4);
+    assert_eq!(allocations[0].utilities.len(), 2);
+    assert!(allocations[1..].iter().all(|a| a.marginal.unwrap() > 0.0));
+
+    // The heat sink is worth more than any booster, but the chaff isn't
+    let best = recommend(&allocations).unwrap();
+    assert_eq!(best.boosters, 2);
+    assert_eq!(best.utilities[0].name, "Heat Sink");
+
+    assert!(allocate(&scenario, &db, 0, &utilities).is_err());
+    assert!("Heat Sink,lots".parse::<UtilitySpec>().is_err());
+    assert!("Heat Sink,NaN".parse::<UtilitySpec>().is_err());
+    assert!("Heat Sink,inf".parse::<UtilitySpec>().is_err());
+}
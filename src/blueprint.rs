@@ -0,0 +1,243 @@
+/*
+Engineering blueprints and experimental effects.
+
+The booster and shield tables assume every module is engineered to a maxed
+grade 5 roll.  To describe what pilots actually own, modules can instead be
+built from base stats plus a blueprint, a grade and a roll fraction, and an
+experimental effect.
+
+`data/Blueprints.csv` holds the range of each attribute a blueprint grade can
+roll, and `data/ExperimentalEffects.csv` the fixed modifiers of each
+experimental effect.  A roll of 0 takes the bottom of a grade's range and 1 the
+top, so grade 5 with a roll of 1 reproduces the built-in tables.
+
+Modifiers stack multiplicatively: strength and regen modifiers scale the base
+value, and resistance modifiers scale the resonance (1 - resistance), so a
++10% resistance modifier on a 50% resistance gives 55%.
+*/
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ModuleKind {
+    Booster,
+    Generator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Attribute {
+    ShieldStrength,
+    RegenRate,
+    ExpRes,
+    KinRes,
+    ThermRes,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BlueprintModifier {
+    pub module: ModuleKind,
+    pub blueprint: String,
+    pub grade: u8,
+    pub attribute: Attribute,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExperimentalModifier {
+    pub module: ModuleKind,
+    pub experimental: String,
+    pub attribute: Attribute,
+    pub value: f64,
+}
+
+/// A blueprint grade and how well it rolled, from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Grade {
+    pub grade: u8,
+    pub roll: f64,
+}
+
+impl Grade {
+    pub const MAX: Grade = Grade {
+        grade: 5,
+        roll: 1.0,
+    };
+
+    pub fn new(grade: u8, roll: f64) -> Result<Self, Error> {
+        if (1..=5).contains(&grade) && (0.0..=1.0).contains(&roll) {
+            Ok(Self { grade, roll })
+        } else {
+            Err(Error::InvalidGrade { grade, roll })
+        }
+    }
+
+    pub fn is_max(&self) -> bool {
+        *self == Self::MAX
+    }
+}
+
+impl Default for Grade {
+    fn default() -> Self {
+        Self::MAX
+    }
+}
+
+impl fmt::Display for Grade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.roll < 1.0 {
+            write!(f, "G{} {:.0}%", self.grade, self.roll * 100.0)
+        } else {
+            write!(f, "G{}", self.grade)
+        }
+    }
+}
+
+/// The combined effect of engineering on a module.  Strength and regen are
+/// multipliers of the base value, resistances multipliers of the resonance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Modifiers {
+    pub shield_strength: f64,
+    pub regen_rate: f64,
+    pub exp_res: f64,
+    pub kin_res: f64,
+    pub therm_res: f64,
+}
+
+impl Default for Modifiers {
+    fn default() -> Self {
+        Self {
+            shield_strength: 1.0,
+            regen_rate: 1.0,
+            exp_res: 1.0,
+            kin_res: 1.0,
+            therm_res: 1.0,
+        }
+    }
+}
+
+impl Modifiers {
+    fn apply(&mut self, attribute: Attribute, value: f64) {
+        match attribute {
+            Attribute::ShieldStrength => self.shield_strength *= 1.0 + value,
+            Attribute::RegenRate => self.regen_rate *= 1.0 + value,
+            Attribute::ExpRes => self.exp_res *= 1.0 - value,
+            Attribute::KinRes => self.kin_res *= 1.0 - value,
+            Attribute::ThermRes => self.therm_res *= 1.0 - value,
+        }
+    }
+}
+
+/// Work out the modifiers of a blueprint at the given grade, plus an
+/// experimental effect.
+pub fn calculate_modifiers(
+    module: ModuleKind,
+    blueprint: &str,
+    grade: Grade,
+    experimental: &str,
+    blueprints: &[BlueprintModifier],
+    experimentals: &[ExperimentalModifier],
+) -> Result<Modifiers, Error> {
+    let mut modifiers = Modifiers::default();
+
+    let mut found = false;
+    for m in blueprints.iter().filter(|m| {
+        m.module == module && m.grade == grade.grade && m.blueprint.eq_ignore_ascii_case(blueprint)
+    }) {
+        modifiers.apply(m.attribute, m.min + (m.max - m.min) * grade.roll);
+        found = true;
+    }
+    if !found {
+        return Err(Error::UnknownBlueprint(blueprint.to_string()));
+    }
+
+    let mut found = false;
+    for m in experimentals
+        .iter()
+        .filter(|m| m.module == module && m.experimental.eq_ignore_ascii_case(experimental))
+    {
+        modifiers.apply(m.attribute, m.value);
+        found = true;
+    }
+    // Not every module lists the lack of an experimental effect
+    if !found && !experimental.eq_ignore_ascii_case("No Experimental Effect") {
+        return Err(Error::UnknownBlueprint(experimental.to_string()));
+    }
+
+    Ok(modifiers)
+}
+
+/// Every blueprint and experimental effect pairing for a module, in table order.
+pub fn variants(
+    module: ModuleKind,
+    blueprints: &[BlueprintModifier],
+    experimentals: &[ExperimentalModifier],
+) -> Vec<(String, String)> {
+    let mut names: Vec<&str> = vec![];
+    for m in blueprints.iter().filter(|m| m.module == module) {
+        if !names.contains(&m.blueprint.as_str()) {
+            names.push(&m.blueprint);
+        }
+    }
+
+    let mut effects: Vec<&str> = vec![];
+    for m in experimentals.iter().filter(|m| m.module == module) {
+        if !effects.contains(&m.experimental.as_str()) {
+            effects.push(&m.experimental);
+        }
+    }
+
+    names
+        .iter()
+        .flat_map(|name| {
+            effects
+                .iter()
+                .map(move |effect| (name.to_string(), effect.to_string()))
+        })
+        .collect()
+}
+
+#[test]
+fn test_blueprints() {
+    let db = crate::Database::builtin().unwrap();
+
+    // Maxed grade 5 reproduces the built-in booster table
+    let boosters = db.engineered_boosters(Grade::MAX).unwrap();
+    assert_eq!(boosters.len(), db.boosters.len());
+    for booster in db.boosters.iter() {
+        let built = boosters
+            .iter()
+            .find(|b| {
+                b.rating == booster.rating
+                    && b.engineering == booster.engineering
+                    && b.experimental == booster.experimental
+            })
+            .unwrap();
+        assert!((built.shield_strength_bonus - booster.shield_strength_bonus).abs() < 1e-3);
+        assert!((built.exp_res_bonus - booster.exp_res_bonus).abs() < 1e-4);
+        assert!((built.kin_res_bonus - booster.kin_res_bonus).abs() < 1e-4);
+        assert!((built.therm_res_bonus - booster.therm_res_bonus).abs() < 1e-4);
+    }
+
+    // A half roll of grade 3 Heavy Duty
+    let modifiers = calculate_modifiers(
+        ModuleKind::Booster,
+        "Heavy Duty",
+        Grade::new(3, 0.5).unwrap(),
+        "No Experimental Effect",
+        &db.blueprint_modifiers,
+        &db.experimental_modifiers,
+    )
+    .unwrap();
+    assert!((modifiers.shield_strength - (1.0 + 0.38 * 0.5)).abs() < 1e-9);
+    assert!((modifiers.kin_res - 1.0).abs() < 1e-9);
+
+    assert!(Grade::new(6, 1.0).is_err());
+    assert!(Grade::new(3, 1.5).is_err());
+}
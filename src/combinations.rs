@@ -6,38 +6,91 @@ Jamie "Entity" van den Berge - https://github.com/ntt
 Ported to Rust by Thomas Hurst - https://github.com/Freaky/
 */
 
+/// The most items selections can be drawn from, as each takes a bit of a u128.
+pub const MAX_ITEMS: usize = 128;
+
 /*
     generates all unique selections of n elements from given set, allowing
     repetitions, with the constraint that any two items in the generated
     selections must appear as pair in allowedPairs in any order. If m is
     specified, each element will appear at most m times in a selection.
 */
-pub fn unique_selections_from_pairs<T, P>(
-    items: &[T],
+pub fn unique_selections_from_pairs<'a, T, P>(
+    items: &'a [T],
     allowed_pairs: &[(T, T)],
-    mut n: usize,
+    n: usize,
+    m: usize,
+    task: P,
+) where
+    T: Sized + PartialEq,
+    P: FnMut(&[&'a T]),
+{
+    unique_limited_selections_from_pairs(items, allowed_pairs, n, m, |_| 0, &[n], task)
+}
+
+/*
+    as above, but items also belong to a group given by group_of, and each
+    selection holds at most limits[group] items from each group.  Used to
+    mix booster ratings, e.g. three A-rated and two C-rated.
+*/
+pub fn unique_limited_selections_from_pairs<'a, T, G, P>(
+    items: &'a [T],
+    allowed_pairs: &[(T, T)],
+    n: usize,
     mut m: usize,
-    mut task: P,
+    group_of: G,
+    limits: &[usize],
+    task: P,
 ) where
     T: Sized + PartialEq,
-    P: FnMut(&[&T]),
+    G: Fn(&T) -> usize,
+    P: FnMut(&[&'a T]),
 {
-    let mut solution: Vec<&T> = vec![&items[0]; n];
+    // sanitize max number of identical items
+    if m == 0 || m > n {
+        m = n;
+    }
+
+    unique_capped_selections_from_pairs(
+        items,
+        allowed_pairs,
+        n,
+        &vec![m; items.len()],
+        group_of,
+        limits,
+        task,
+    )
+}
 
+/*
+    as above, but rather than one m for every item, caps[i] is the most
+    times items[i] may appear, which may be 0 to leave it out entirely.
+    Used to stick to the boosters a commander owns.
+*/
+pub fn unique_capped_selections_from_pairs<'a, T, G, P>(
+    items: &'a [T],
+    allowed_pairs: &[(T, T)],
+    mut n: usize,
+    caps: &[usize],
+    group_of: G,
+    limits: &[usize],
+    mut task: P,
+) where
+    T: Sized + PartialEq,
+    G: Fn(&T) -> usize,
+    P: FnMut(&[&'a T]),
+{
     if n == 0 {
         // ask a silly question...
-        task(&solution[..]);
+        task(&[]);
         return;
     }
 
-    // sanitize max number of identical items
-    if m == 0 || m > n {
-        m = n;
-    }
+    let mut solution: Vec<&'a T> = vec![&items[0]; n];
 
     // create bitmask map telling which items are ok to use with which others.
-    assert!(items.len() <= 32); // switch to u64 for more items
-    let mut allowed: Vec<u32> = vec![0; items.len()];
+    assert!(items.len() <= MAX_ITEMS);
+    let mut allowed: Vec<u128> = vec![0; items.len()];
     for (a, b) in allowed_pairs.iter() {
         let a_idx = items
             .iter()
@@ -55,13 +108,28 @@ pub fn unique_selections_from_pairs<T, P>(
 
     let mut item_count = vec![0; items.len()]; // individual item count in current solution
 
+    // items from each group in current solution, and bitmasks of each group's
+    // items, which are dropped from the allowed items once a group is full.
+    let group: Vec<usize> = items.iter().map(group_of).collect();
+    let mut group_count = vec![0; limits.len()];
+    let mut group_mask: Vec<u128> = vec![0; limits.len()];
+    for (idx, &g) in group.iter().enumerate() {
+        group_mask[g] |= 1 << idx;
+    }
+
     let mut depth = 0; // current solution depth
     n -= 1; // n is now the deepest allowed depth
 
-    // track allowed items at each depth. root level items always allow all items
+    // track allowed items at each depth. root level items allow all items,
+    // bar any in groups with no room at all
+    let mut mask = u128::MAX;
+    for (g, &limit) in limits.iter().enumerate() {
+        if limit == 0 {
+            mask &= !group_mask[g];
+        }
+    }
     let mut allowed_mask = vec![0; n + 1];
-    allowed_mask[0] = std::u32::MAX;
-    let mut mask = std::u32::MAX;
+    allowed_mask[0] = mask;
 
     let mut idx = 0;
     loop {
@@ -73,6 +141,7 @@ pub fn unique_selections_from_pairs<T, P>(
                 depth -= 1;
                 idx = count[depth];
                 item_count[idx] -= 1;
+                group_count[group[idx]] -= 1;
                 idx += 1;
                 count[depth] = idx;
                 mask = allowed_mask[depth];
@@ -83,7 +152,7 @@ pub fn unique_selections_from_pairs<T, P>(
         }
 
         // see if the current solution so far satisfies the constraints.
-        if item_count[idx] == m || mask & (1 << idx) == 0 {
+        if item_count[idx] >= caps[idx] || mask & (1 << idx) == 0 {
             idx += 1;
             continue;
         }
@@ -91,7 +160,7 @@ pub fn unique_selections_from_pairs<T, P>(
         if depth == n {
             // generate solutions with the remaining items that are allowed.
             while idx < items.len() {
-                if mask & (1 << idx) != 0 {
+                if mask & (1 << idx) != 0 && item_count[idx] < caps[idx] {
                     solution[depth] = &items[idx];
                     task(&solution[..]);
                 }
@@ -105,6 +174,10 @@ pub fn unique_selections_from_pairs<T, P>(
 
         // update solution at current depth
         item_count[idx] += 1;
+        group_count[group[idx]] += 1;
+        if group_count[group[idx]] == limits[group[idx]] {
+            mask &= !group_mask[group[idx]];
+        }
         solution[depth] = &items[idx];
 
         // go deeper and update the bitmask of allowed items at the new depth.
@@ -0,0 +1,313 @@
+/*
+Hard constraints on the result.
+
+Some roles need a loadout with certain properties whatever the attacker does,
+such as a wing asking for a floor on kinetic resistance.  Constraints rule out
+loadouts which don't meet them before they're scored, just as the regen time
+limit does.
+
+Besides the dedicated options, a constraint can be written as
+`<metric> <op> <value>` with `>=` or `<=`, for example `kin_res >= 0.5` or
+`regen_time <= 60`, using the metrics:
+
+  hp                         shield hit points, Mj
+  regen_rate                 Mj/s
+  regen_time                 seconds to regenerate from 50%
+  exp_res, kin_res,          resistance to each damage type, as a fraction, so
+  therm_res, caus_res        0.5 is 50%
+  exp_ehp, kin_ehp,          hit points against only that damage type, Mj
+  therm_ehp, caus_ehp
+  ehp                        hit points against the attacker's damage mix, Mj
+
+Hit points include Guardian Shield Reinforcements, but not Shield Cell Banks.
+*/
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::objective::effective_hit_points;
+use crate::{calculate_regen_time, Error, LoadoutStat, Scenario};
+
+/// A property of a loadout a constraint limits.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Metric {
+    HitPoints,
+    RegenRate,
+    RegenTime,
+    ExpRes,
+    KinRes,
+    ThermRes,
+    CausRes,
+    ExpEhp,
+    KinEhp,
+    ThermEhp,
+    CausEhp,
+    Ehp,
+}
+
+const METRICS: [(&str, Metric); 12] = [
+    ("hp", Metric::HitPoints),
+    ("regen_rate", Metric::RegenRate),
+    ("regen_time", Metric::RegenTime),
+    ("exp_res", Metric::ExpRes),
+    ("kin_res", Metric::KinRes),
+    ("therm_res", Metric::ThermRes),
+    ("caus_res", Metric::CausRes),
+    ("exp_ehp", Metric::ExpEhp),
+    ("kin_ehp", Metric::KinEhp),
+    ("therm_ehp", Metric::ThermEhp),
+    ("caus_ehp", Metric::CausEhp),
+    ("ehp", Metric::Ehp),
+];
+
+impl Metric {
+    /// The metric's value for a loadout.
+    pub fn value(self, scenario: &Scenario, stats: &LoadoutStat) -> f64 {
+        match self {
+            Metric::HitPoints => stats.hit_points,
+            Metric::RegenRate => stats.regen_rate,
+            Metric::RegenTime => calculate_regen_time(stats),
+            Metric::ExpRes => 1.0 - stats.exp_res,
+            Metric::KinRes => 1.0 - stats.kin_res,
+            Metric::ThermRes => 1.0 - stats.therm_res,
+            Metric::CausRes => 1.0 - stats.caus_res,
+            Metric::ExpEhp => stats.hit_points / stats.exp_res,
+            Metric::KinEhp => stats.hit_points / stats.kin_res,
+            Metric::ThermEhp => stats.hit_points / stats.therm_res,
+            Metric::CausEhp => stats.hit_points / stats.caus_res,
+            Metric::Ehp => effective_hit_points(scenario, stats),
+        }
+    }
+
+    /// Does the metric depend on resistance to a particular damage type?
+    fn uses_resistance(self) -> bool {
+        !matches!(
+            self,
+            Metric::HitPoints | Metric::RegenRate | Metric::RegenTime
+        )
+    }
+
+    fn name(self) -> &'static str {
+        METRICS
+            .iter()
+            .find(|&&(_, metric)| metric == self)
+            .map(|&(name, _)| name)
+            .unwrap_or_default()
+    }
+}
+
+/// A limit on one metric, such as `kin_res >= 0.5`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Constraint {
+    pub metric: Metric,
+    /// True for a lower limit, false for an upper one
+    pub at_least: bool,
+    pub value: f64,
+}
+
+impl Constraint {
+    pub fn at_least(metric: Metric, value: f64) -> Self {
+        Self {
+            metric,
+            at_least: true,
+            value,
+        }
+    }
+
+    /// Does a loadout with these stats meet the constraint?
+    pub fn allows(&self, scenario: &Scenario, stats: &LoadoutStat) -> bool {
+        let value = self.metric.value(scenario, stats);
+        if self.at_least {
+            value >= self.value
+        } else {
+            value <= self.value
+        }
+    }
+}
+
+impl FromStr for Constraint {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::InvalidConstraint(s.to_string());
+
+        let (at_least, op) = match (s.find(">="), s.find("<=")) {
+            (Some(i), None) => (true, i),
+            (None, Some(i)) => (false, i),
+            _ => return Err(err()),
+        };
+
+        let name = s[..op].trim().to_ascii_lowercase();
+        let metric = METRICS
+            .iter()
+            .find(|&&(n, _)| n == name)
+            .map(|&(_, metric)| metric)
+            .ok_or_else(err)?;
+        let value = s[op + 2..].trim().parse().map_err(|_| err())?;
+
+        Ok(Self {
+            metric,
+            at_least,
+            value,
+        })
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.metric.name(),
+            if self.at_least { ">=" } else { "<=" },
+            self.value
+        )
+    }
+}
+
+/// Every constraint the scenario sets, from the dedicated options and `--constraint`.
+pub fn constraints(scenario: &Scenario) -> Vec<Constraint> {
+    let floors = [
+        (Metric::HitPoints, scenario.min_hp),
+        (Metric::ExpRes, scenario.min_exp_res),
+        (Metric::KinRes, scenario.min_kin_res),
+        (Metric::ThermRes, scenario.min_therm_res),
+        (Metric::ExpEhp, scenario.min_explosive_ehp),
+        (Metric::KinEhp, scenario.min_kinetic_ehp),
+        (Metric::ThermEhp, scenario.min_thermal_ehp),
+    ];
+
+    floors
+        .iter()
+        .filter_map(|&(metric, floor)| floor.map(|floor| Constraint::at_least(metric, floor)))
+        .chain(scenario.constraints.iter().cloned())
+        .collect()
+}
+
+/// Does any constraint depend on resistances?  If so, a booster resisting a
+/// damage type the attacker doesn't deal can still be needed to meet it.
+pub fn limits_resistance(scenario: &Scenario) -> bool {
+    constraints(scenario)
+        .iter()
+        .any(|c| c.metric.uses_resistance())
+}
+
+/// Counts loadouts meeting each constraint, to explain an empty result.
+#[derive(Debug, Clone)]
+pub struct Tally {
+    pub constraints: Vec<Constraint>,
+    met: Vec<usize>,
+    checked: usize,
+}
+
+impl Tally {
+    pub fn new(scenario: &Scenario) -> Self {
+        let constraints = constraints(scenario);
+        let met = vec![0; constraints.len()];
+        Self {
+            constraints,
+            met,
+            checked: 0,
+        }
+    }
+
+    /// Does a loadout with these stats meet every constraint?
+    pub fn allows(&mut self, scenario: &Scenario, stats: &LoadoutStat) -> bool {
+        self.checked += 1;
+        let mut allowed = true;
+        for (constraint, met) in self.constraints.iter().zip(self.met.iter_mut()) {
+            if constraint.allows(scenario, stats) {
+                *met += 1;
+            } else {
+                allowed = false;
+            }
+        }
+        allowed
+    }
+
+    /// The error for a search which found nothing, if constraints were why.
+    ///
+    /// Names the constraints no loadout meets even on its own, if any.  If
+    /// the regen time limit or power budget left no loadout to check, the
+    /// constraints aren't to blame.
+    pub fn infeasible(&self) -> Option<Error> {
+        if self.constraints.is_empty() || self.checked == 0 {
+            return None;
+        }
+
+        Some(Error::Infeasible(
+            self.constraints
+                .iter()
+                .zip(self.met.iter())
+                .filter(|&(_, &met)| met == 0)
+                .map(|(constraint, _)| constraint.to_string())
+                .collect(),
+        ))
+    }
+}
+
+#[test]
+fn test_constraints() {
+    let db = crate::Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        shield_booster_count: 2,
+        kinetic_dps: 100.0,
+        thermal_dps: 50.0,
+        ..Scenario::default()
+    };
+
+    let best = crate::optimise(&scenario, &db).unwrap().results.remove(0);
+    assert!(1.0 - best.stats.kin_res < 0.5);
+
+    // A kinetic resistance floor costs survival time
+    let floored = Scenario {
+        min_kin_res: Some(0.5),
+        constraints: vec!["hp >= 1000".parse().unwrap()],
+        ..scenario.clone()
+    };
+    let result = crate::optimise(&floored, &db).unwrap().results.remove(0);
+    assert!(1.0 - result.stats.kin_res >= 0.5);
+    assert!(result.stats.hit_points >= 1000.0);
+    assert!(result.survival_time < best.survival_time);
+
+    let impossible = Scenario {
+        constraints: vec!["therm_res >= 0.9".parse().unwrap()],
+        ..scenario.clone()
+    };
+    match crate::optimise(&impossible, &db) {
+        Err(Error::Infeasible(unmet)) => assert_eq!(unmet, vec!["therm_res >= 0.9"]),
+        other => panic!("expected infeasible, got {:?}", other.map(|o| o.results)),
+    }
+
+    // A regen time limit nothing meets isn't the constraints' fault
+    let regen = Scenario {
+        regen_time_limit: Some(1.0),
+        min_hp: Some(100.0),
+        ..scenario.clone()
+    };
+    assert!(crate::optimise(&regen, &db).unwrap().results.is_empty());
+
+    // Meeting a floor on a resistance the attacker doesn't test needs
+    // boosters which resist it
+    let blast = Scenario {
+        shield_booster_count: 3,
+        kinetic_dps: 100.0,
+        thermal_dps: 0.0,
+        min_exp_res: Some(0.75),
+        ..scenario
+    };
+    let result = crate::optimise(&blast, &db).unwrap().results.remove(0);
+    assert!(1.0 - result.stats.exp_res >= 0.75);
+    assert!(result
+        .boosters
+        .iter()
+        .any(|b| b.engineering == "Blast Resistance"));
+
+    let constraint: Constraint = "REGEN_TIME<=60".parse().unwrap();
+    assert_eq!(constraint.to_string(), "regen_time <= 60");
+    assert!("kin_res > 0.5".parse::<Constraint>().is_err());
+    assert!("speed >= 1".parse::<Constraint>().is_err());
+}
@@ -0,0 +1,241 @@
+/*
+Evaluating a known loadout.
+
+Rather than searching, score a single fully specified loadout against the
+scenario, for example to compare a ship as it's currently fitted with the
+best the search can find.
+
+Named modules are looked up among the search's candidates, so they're
+engineered to the scenario's grades and derived for its hull mass.
+*/
+
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::journal::Loadout;
+use crate::{
+    calculate_actual_dps, calculate_booster_stats, calculate_cell_mj, calculate_cell_power,
+    calculate_loadout_stats, hull, Candidates, Database, Error, Scenario, ShieldBooster,
+    TestResult,
+};
+
+/// A shield generator, written as `<type>,<engineering>[,<experimental>]`,
+/// for example `Prismatic,Reinforced,Hi-Cap`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShieldSpec {
+    pub kind: String,
+    pub engineering: String,
+    pub experimental: String,
+}
+
+impl FromStr for ShieldSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::UnknownShield(s.to_string());
+        let mut parts = s.split(',').map(str::trim);
+
+        let kind = parts.next().filter(|p| !p.is_empty()).ok_or_else(err)?;
+        let engineering = parts.next().ok_or_else(err)?;
+        let experimental = parts.next().unwrap_or("No Experimental Effect");
+
+        if parts.next().is_some() {
+            return Err(err());
+        }
+
+        Ok(Self {
+            kind: kind.to_string(),
+            engineering: engineering.to_string(),
+            experimental: experimental.to_string(),
+        })
+    }
+}
+
+/// A shield booster, written as `[<rating>,]<engineering>[,<experimental>]`,
+/// for example `Heavy Duty,Super Capacitors` or `C,Resistance Augmented`.
+/// Without a rating, the scenario's is used.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BoosterSpec {
+    pub rating: Option<char>,
+    pub engineering: String,
+    pub experimental: String,
+}
+
+impl FromStr for BoosterSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::UnknownBooster(s.to_string());
+        let mut parts = s.split(',').map(str::trim).peekable();
+
+        let rating = match parts.peek() {
+            Some(part) if part.len() == 1 => parts.next().and_then(|r| r.chars().next()),
+            _ => None,
+        }
+        .map(|r| r.to_ascii_uppercase());
+
+        let engineering = parts.next().filter(|p| !p.is_empty()).ok_or_else(err)?;
+        let experimental = parts.next().unwrap_or("No Experimental Effect");
+
+        if parts.next().is_some() {
+            return Err(err());
+        }
+
+        Ok(Self {
+            rating,
+            engineering: engineering.to_string(),
+            experimental: experimental.to_string(),
+        })
+    }
+}
+
+impl BoosterSpec {
+    /// Is `booster` this one, of any rating if none is given?
+    pub fn matches(&self, booster: &ShieldBooster) -> bool {
+        self.rating.map(|r| r == booster.rating).unwrap_or(true)
+            && booster.engineering.eq_ignore_ascii_case(&self.engineering)
+            && booster
+                .experimental
+                .eq_ignore_ascii_case(&self.experimental)
+    }
+}
+
+/// Look up named modules for the scenario's ship, shield class and booster ratings.
+pub fn resolve_loadout(
+    scenario: &Scenario,
+    db: &Database,
+    shield: &ShieldSpec,
+    boosters: &[BoosterSpec],
+) -> Result<Loadout, Error> {
+    // The loadout is as fitted, whatever modules the search is told to use
+    let scenario = Scenario {
+        only_shield_types: vec![],
+        require_shield: None,
+        require_boosters: vec![],
+        booster_caps: vec![],
+        inventory: None,
+        max_duplicates: 0,
+        exclude_blueprints: vec![],
+        exclude_experimentals: vec![],
+        ..scenario.clone()
+    };
+    let candidates = Candidates::select(&scenario, db, false)?;
+
+    let generator = candidates
+        .generators
+        .iter()
+        .find(|g| {
+            g.kind.eq_ignore_ascii_case(&shield.kind)
+                && g.engineering.eq_ignore_ascii_case(&shield.engineering)
+                && g.experimental.eq_ignore_ascii_case(&shield.experimental)
+        })
+        .ok_or_else(|| {
+            Error::UnknownShield(format!(
+                "{},{},{}",
+                shield.kind, shield.engineering, shield.experimental
+            ))
+        })?;
+
+    let boosters = boosters
+        .iter()
+        .map(|spec| {
+            candidates
+                .boosters
+                .iter()
+                .find(|b| spec.matches(b))
+                .cloned()
+                .ok_or_else(|| {
+                    Error::UnknownBooster(format!("{},{}", spec.engineering, spec.experimental))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Loadout {
+        ship: generator.ship.clone(),
+        generator: generator.clone(),
+        boosters,
+    })
+}
+
+/// Score a single loadout against the scenario's attacker.
+pub fn evaluate(
+    scenario: &Scenario,
+    db: &Database,
+    loadout: &Loadout,
+) -> Result<TestResult, Error> {
+    let cell_banks = db.resolve_cell_banks(&scenario.cell_banks)?;
+    let hull = db.resolve_hull(scenario)?;
+
+    let boosters: Vec<&ShieldBooster> = loadout.boosters.iter().collect();
+    let mut stats =
+        calculate_loadout_stats(&loadout.generator, &calculate_booster_stats(&boosters));
+    stats.hit_points += scenario.reinforced_mj;
+    stats.power_draw += calculate_cell_power(&cell_banks);
+
+    let actual_dps = calculate_actual_dps(scenario, &stats);
+    let survival_time =
+        (stats.hit_points + calculate_cell_mj(scenario, &cell_banks, &stats)) / actual_dps;
+
+    Ok(TestResult {
+        actual_dps,
+        survival_time,
+        time_to_kill: hull
+            .as_ref()
+            .map(|hull| hull::calculate_time_to_kill(survival_time, hull)),
+        shield: loadout.generator.clone(),
+        boosters: loadout.boosters.clone(),
+        stats,
+    })
+}
+
+#[test]
+fn test_evaluate() {
+    let db = Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        shield_booster_count: 2,
+        explosive_dps: 50.0,
+        kinetic_dps: 50.0,
+        thermal_dps: 50.0,
+        ..Scenario::default()
+    };
+
+    // Evaluating the optimum reproduces its result
+    let outcome = crate::optimise(&scenario, &db).unwrap();
+    let best = outcome.best().unwrap();
+    let shield = ShieldSpec {
+        kind: best.shield.kind.clone(),
+        engineering: best.shield.engineering.clone(),
+        experimental: best.shield.experimental.clone(),
+    };
+    let boosters: Vec<BoosterSpec> = best
+        .boosters
+        .iter()
+        .map(|b| BoosterSpec {
+            rating: None,
+            engineering: b.engineering.clone(),
+            experimental: b.experimental.clone(),
+        })
+        .collect();
+
+    let loadout = resolve_loadout(&scenario, &db, &shield, &boosters).unwrap();
+    let result = evaluate(&scenario, &db, &loadout).unwrap();
+    assert!((result.survival_time - best.survival_time).abs() < 1e-9);
+
+    // Anything else does no better
+    let worse = resolve_loadout(
+        &scenario,
+        &db,
+        &"normal,thermal resistant,fast charge".parse().unwrap(),
+        &["Heavy Duty".parse().unwrap(), "Heavy Duty".parse().unwrap()],
+    )
+    .unwrap();
+    assert!(evaluate(&scenario, &db, &worse).unwrap().survival_time < best.survival_time);
+
+    assert!("Prismatic".parse::<ShieldSpec>().is_err());
+    let spec: BoosterSpec = "c, Heavy Duty".parse().unwrap();
+    assert_eq!(spec.rating, Some('C'));
+    assert_eq!(spec.experimental, "No Experimental Effect");
+    assert!(resolve_loadout(&scenario, &db, &shield, &["Nope".parse().unwrap()]).is_err());
+}
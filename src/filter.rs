@@ -0,0 +1,288 @@
+/*
+Choosing which modules the search may use.
+
+Modules can be ruled out by blueprint or experimental effect, and generators
+limited to some types or pinned to one.  Boosters already owned can be pinned
+too, so the search only fills the remaining slots, and the number of copies
+of any booster capped, such as to the number owned.  With an inventory, the
+search takes only owned modules, each capped at the number owned.
+
+Boosters resisting a damage type the attacker doesn't deal are dropped in the
+same way, as if their blueprint were excluded, unless something other than
+the attacker decides the result, such as a constraint on resistances.
+*/
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::constraint;
+use crate::evaluate::{BoosterSpec, ShieldSpec};
+use crate::{Error, Scenario, ShieldBooster, ShieldGenerator};
+
+/// Booster blueprints which only resist one damage type, and that type's DPS.
+fn resistance_blueprints(scenario: &Scenario) -> [(&'static str, f64); 3] {
+    [
+        ("Blast Resistance", scenario.explosive_dps),
+        ("Kinetic Resistance", scenario.kinetic_dps),
+        ("Thermal Resistance", scenario.thermal_dps),
+    ]
+}
+
+/// A number of one booster, written as `[<count>x ]<booster>` in the form
+/// `--booster` takes, for example `3x Heavy Duty,Super Capacitors`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BoosterCount {
+    pub count: usize,
+    pub booster: BoosterSpec,
+}
+
+impl FromStr for BoosterCount {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (count, rest) = match s.split_once(char::is_whitespace) {
+            Some((word, rest)) if word.ends_with(&['x', 'X'][..]) => {
+                match word[..word.len() - 1].parse() {
+                    Ok(count) => (count, rest),
+                    Err(_) => (1, s),
+                }
+            }
+            _ => (1, s),
+        };
+
+        Ok(Self {
+            count,
+            booster: rest.parse()?,
+        })
+    }
+}
+
+/// Read booster caps from a file with one per line, in the form `--booster-cap`
+/// takes.  Blank lines and lines starting with `#` are skipped.
+pub fn read_booster_caps(path: &Path) -> Result<Vec<BoosterCount>, Error> {
+    fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::parse)
+        .collect()
+}
+
+/// The rules deciding which modules are candidates.
+#[derive(Debug, Clone)]
+pub struct ModuleFilter<'a> {
+    shield_types: &'a [String],
+    shield: Option<&'a ShieldSpec>,
+    blueprints: Vec<&'a str>,
+    experimentals: &'a [String],
+    force_experimental: bool,
+}
+
+impl<'a> ModuleFilter<'a> {
+    /// The scenario's filters.  If `damage_filter` is set, boosters
+    /// specialising in damage types the attacker doesn't use are dropped.
+    pub fn new(scenario: &'a Scenario, damage_filter: bool) -> Self {
+        let mut blueprints: Vec<&str> =
+            scenario.exclude_blueprints.iter().map(|b| &b[..]).collect();
+        if damage_filter && scenario.prefilter() && !constraint::limits_resistance(scenario) {
+            blueprints.extend(
+                resistance_blueprints(scenario)
+                    .iter()
+                    .filter(|&&(_, dps)| dps == 0.0)
+                    .map(|&(blueprint, _)| blueprint),
+            );
+        }
+
+        Self {
+            shield_types: &scenario.only_shield_types,
+            shield: scenario.require_shield.as_ref(),
+            blueprints,
+            experimentals: &scenario.exclude_experimentals,
+            force_experimental: scenario.force_experimental,
+        }
+    }
+
+    fn allows_modification(&self, engineering: &str, experimental: &str) -> bool {
+        !self
+            .blueprints
+            .iter()
+            .any(|b| b.eq_ignore_ascii_case(engineering))
+            && !self
+                .experimentals
+                .iter()
+                .any(|e| e.eq_ignore_ascii_case(experimental))
+    }
+
+    pub fn allows_shield(&self, shield: &ShieldGenerator) -> bool {
+        let pinned = match self.shield {
+            Some(spec) => {
+                shield.kind.eq_ignore_ascii_case(&spec.kind)
+                    && shield.engineering.eq_ignore_ascii_case(&spec.engineering)
+                    && shield.experimental.eq_ignore_ascii_case(&spec.experimental)
+            }
+            None => true,
+        };
+
+        pinned
+            && (self.shield_types.is_empty()
+                || self
+                    .shield_types
+                    .iter()
+                    .any(|t| t.eq_ignore_ascii_case(&shield.kind)))
+            && self.allows_modification(&shield.engineering, &shield.experimental)
+    }
+
+    pub fn allows_booster(&self, booster: &ShieldBooster) -> bool {
+        (!self.force_experimental || booster.experimental != "No Experimental Effect")
+            && self.allows_modification(&booster.engineering, &booster.experimental)
+    }
+}
+
+/// The error for pinning more boosters, of `rating` if given, than fit.
+fn too_many(scenario: &Scenario, rating: Option<char>) -> Error {
+    let rated = |r: Option<char>| rating.is_none() || r == rating;
+    Error::TooManyPinnedBoosters {
+        rating,
+        pinned: scenario
+            .require_boosters
+            .iter()
+            .filter(|p| rated(p.booster.rating))
+            .map(|p| p.count)
+            .sum(),
+        slots: scenario
+            .booster_rating_limits()
+            .iter()
+            .filter(|&&(r, _)| rated(Some(r)))
+            .map(|&(_, count)| count)
+            .sum(),
+    }
+}
+
+/// Find the scenario's pinned boosters among `boosters`, regardless of filters.
+///
+/// Pinned boosters without a rating take the best rating with a slot to spare.
+pub fn pinned_boosters(
+    scenario: &Scenario,
+    boosters: &[ShieldBooster],
+) -> Result<Vec<ShieldBooster>, Error> {
+    let mut free = scenario.booster_rating_limits();
+    let mut pinned = vec![];
+
+    for pin in scenario.require_boosters.iter() {
+        let spec = &pin.booster;
+        for _ in 0..pin.count {
+            let slot = free
+                .iter_mut()
+                .find(|(rating, count)| {
+                    *count > 0 && spec.rating.map(|r| r == *rating).unwrap_or(true)
+                })
+                .ok_or_else(|| too_many(scenario, spec.rating))?;
+            slot.1 -= 1;
+            let rating = slot.0;
+
+            let booster = boosters
+                .iter()
+                .find(|b| b.rating == rating && spec.matches(b))
+                .ok_or_else(|| {
+                    Error::UnknownBooster(format!("{},{}", spec.engineering, spec.experimental))
+                })?;
+            pinned.push(booster.clone());
+        }
+    }
+
+    Ok(pinned)
+}
+
+/// The most times the search may add each of `boosters` alongside the
+/// `pinned` ones, under the scenario's duplicate limit, booster caps and
+/// inventory.
+pub fn booster_caps(
+    scenario: &Scenario,
+    boosters: &[ShieldBooster],
+    pinned: &[ShieldBooster],
+) -> Vec<usize> {
+    boosters
+        .iter()
+        .map(|booster| {
+            let duplicates = Some(scenario.max_duplicates).filter(|&m| m > 0);
+            let capped = scenario
+                .booster_caps
+                .iter()
+                .filter(|cap| cap.booster.matches(booster))
+                .map(|cap| cap.count)
+                .min();
+            let owned = scenario
+                .inventory
+                .as_ref()
+                .map(|inventory| inventory.booster_count(booster));
+
+            match duplicates.into_iter().chain(capped).chain(owned).min() {
+                None => usize::MAX,
+                Some(cap) => cap.saturating_sub(pinned.iter().filter(|&b| b == booster).count()),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_filters() {
+    let db = crate::Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        shield_booster_count: 3,
+        kinetic_dps: 100.0,
+        thermal_dps: 50.0,
+        ..Scenario::default()
+    };
+
+    let free = crate::optimise(&scenario, &db).unwrap();
+    let pinned = Scenario {
+        require_boosters: vec!["2x Resistance Augmented,Thermo Block".parse().unwrap()],
+        only_shield_types: vec!["Bi-Weave".to_string()],
+        exclude_experimentals: vec!["Fast Charge".to_string()],
+        ..scenario.clone()
+    };
+    let outcome = crate::optimise(&pinned, &db).unwrap();
+    let best = outcome.best().unwrap();
+    assert_eq!(best.shield.kind, "Bi-Weave");
+    assert_ne!(best.shield.experimental, "Fast Charge");
+    assert_eq!(
+        best.boosters
+            .iter()
+            .filter(|b| b.engineering == "Resistance Augmented" && b.experimental == "Thermo Block")
+            .count(),
+        2
+    );
+    assert!(best.survival_time < free.best().unwrap().survival_time);
+
+    // Only one slot left to search
+    assert!(outcome.combinations < free.combinations);
+
+    // Pinned boosters count towards their cap
+    let capped = Scenario {
+        max_duplicates: 1,
+        require_boosters: vec!["Heavy Duty,Super Capacitors".parse().unwrap()],
+        booster_caps: vec!["1x Heavy Duty,Super Capacitors".parse().unwrap()],
+        ..scenario.clone()
+    };
+    let best = crate::optimise(&capped, &db).unwrap().results.remove(0);
+    assert!(best.boosters.iter().all(|a| {
+        best.boosters
+            .iter()
+            .filter(|b| b.engineering == a.engineering && b.experimental == a.experimental)
+            .count()
+            == 1
+    }));
+
+    let pin: BoosterCount = "Heavy Duty".parse().unwrap();
+    assert_eq!(pin.count, 1);
+    let too_many = Scenario {
+        require_boosters: vec!["4x Heavy Duty".parse().unwrap()],
+        ..scenario
+    };
+    assert!(crate::optimise(&too_many, &db).is_err());
+}
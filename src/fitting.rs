@@ -0,0 +1,491 @@
+/*
+Internal slot fitting.
+
+Rather than taking the shield class, Shield Cell Banks, Guardian Shield
+Reinforcement Packages and Hull Reinforcement Packages as given, choose which
+of the ship's optional internal slots hold each of them.
+
+Each slot holds the largest module of its kind which fits: an A-rated,
+unengineered Shield Cell Bank, a D-rated Guardian Shield Reinforcement Package
+from `data/GuardianShieldReinforcements.csv`, or a D-rated Hull Reinforcement
+Package.  Hull reinforcements only count when the scenario has a hull, given
+by `--bulkheads` or `--hrp`; otherwise those slots are left empty.
+
+Against a given attacker, a loadout's survival time depends only on its hit
+points and the rate the attacker drains them, and the other internals only
+add Mj.  So for each shield class it's enough to keep, for each generator,
+the loadouts no other beats on both, and score just those against every way
+of filling the remaining slots.  Objectives which also score effective hit
+points or regen time keep that score as a third axis.
+*/
+
+use std::collections::HashMap;
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::constraint;
+use crate::hull::HullReinforcementSpec;
+use crate::objective::{self, Objective};
+use crate::pareto::{booster_front, BoosterFront};
+use crate::scb::CellBankSpec;
+use crate::{
+    beats, calculate_actual_dps, calculate_cell_mj, calculate_cell_power, calculate_loadout_stats,
+    calculate_regen_time, compare_results, filter_booster_pairs, hull, kdtree, Candidates,
+    Database, Error, LoadoutStat, Scenario, ShieldGenerator, TestResult,
+};
+
+/// Guardian Shield Reinforcement Package.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all(deserialize = "PascalCase"))]
+pub struct GuardianReinforcement {
+    pub class: u8,
+    pub rating: char,
+    /// Mj added to the shield
+    pub shield_reinforcement: f64,
+    /// MW drawn while powered
+    pub power_draw: f64,
+}
+
+/// What an internal slot holds.
+#[derive(Debug, Clone, Serialize)]
+pub enum SlotModule {
+    ShieldGenerator(u8),
+    CellBank(CellBankSpec),
+    GuardianReinforcement(GuardianReinforcement),
+    HullReinforcement(HullReinforcementSpec),
+    Empty,
+}
+
+/// The best way found to fill a ship's internal slots.
+#[derive(Debug, Clone, Serialize)]
+pub struct Fitting {
+    /// Each slot's size and what it holds, in the order given
+    pub slots: Vec<(u8, SlotModule)>,
+    pub result: TestResult,
+}
+
+impl Fitting {
+    /// The scenario with this fitting's internals in place of its own.
+    ///
+    /// Guardian reinforcements become `reinforced_mj`, and their power draw is
+    /// added to `power_used`.
+    pub fn apply(&self, scenario: &Scenario) -> Scenario {
+        with_internals(scenario, &self.slots)
+    }
+}
+
+fn with_internals(scenario: &Scenario, slots: &[(u8, SlotModule)]) -> Scenario {
+    let mut scenario = clear_internals(scenario);
+    for (_, module) in slots.iter() {
+        match module {
+            SlotModule::ShieldGenerator(class) => scenario.shield_class = Some(*class),
+            SlotModule::CellBank(spec) => scenario.cell_banks.push(spec.clone()),
+            SlotModule::GuardianReinforcement(gsrp) => {
+                scenario.reinforced_mj += gsrp.shield_reinforcement;
+                scenario.power_used += gsrp.power_draw;
+            }
+            SlotModule::HullReinforcement(spec) => scenario.hull_reinforcements.push(*spec),
+            SlotModule::Empty => (),
+        }
+    }
+    scenario
+}
+
+/// The scenario without any of the internals fitting chooses.
+fn clear_internals(scenario: &Scenario) -> Scenario {
+    let has_hull = scenario.bulkheads.is_some() || !scenario.hull_reinforcements.is_empty();
+    Scenario {
+        shield_class: None,
+        cell_banks: vec![],
+        shield_cell_mj: 0.0,
+        reinforced_mj: 0.0,
+        hull_reinforcements: vec![],
+        bulkheads: scenario
+            .bulkheads
+            .clone()
+            .or_else(|| Some("Lightweight".to_string()).filter(|_| has_hull)),
+        ..scenario.clone()
+    }
+}
+
+/// Modules other than the shield generator, in a fixed order so slots of the
+/// same size are only filled one way.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Kind {
+    CellBank,
+    Guardian,
+    Other,
+}
+
+const KINDS: [Kind; 3] = [Kind::CellBank, Kind::Guardian, Kind::Other];
+
+/// A loadout no other with the same generator beats on hit points and drain rate.
+struct Point {
+    shield: ShieldGenerator,
+    booster_id: usize,
+    stats: LoadoutStat,
+    actual_dps: f64,
+}
+
+/// Loadouts worth scoring for a shield class, with hit points including
+/// `reinforced_mj`, which only matters under a regen time limit, constraints
+/// or an objective with a static score.
+fn loadout_front(
+    scenario: &Scenario,
+    generators: &[ShieldGenerator],
+    boosters: &BoosterFront,
+    reinforced_mj: f64,
+) -> Vec<Point> {
+    let constraints = constraint::constraints(scenario);
+    let mut points = vec![];
+
+    for shield in generators.iter() {
+        let mut own: Vec<Point> = boosters
+            .front
+            .iter()
+            .filter_map(|&id| {
+                let mut stats = calculate_loadout_stats(shield, &boosters.stats[id]);
+                stats.hit_points += reinforced_mj;
+
+                if scenario
+                    .regen_time_limit
+                    .map(|limit| calculate_regen_time(&stats) > limit)
+                    .unwrap_or(false)
+                    || !constraints.iter().all(|c| c.allows(scenario, &stats))
+                {
+                    return None;
+                }
+
+                Some(Point {
+                    shield: shield.clone(),
+                    booster_id: id,
+                    actual_dps: calculate_actual_dps(scenario, &stats),
+                    stats,
+                })
+            })
+            .collect();
+
+        // Objectives scoring more than hit points and drain need a third axis
+        if objective::has_static_score(scenario) {
+            let metrics: Vec<Vec<f64>> = own
+                .iter()
+                .map(|point| {
+                    vec![
+                        point.stats.hit_points,
+                        -point.actual_dps,
+                        objective::static_score(scenario, &point.stats).unwrap_or(0.0),
+                    ]
+                })
+                .collect();
+            let keep = kdtree::non_dominated(&metrics);
+            let mut own: Vec<Option<Point>> = own.into_iter().map(Some).collect();
+            points.extend(keep.into_iter().filter_map(|id| own[id].take()));
+            continue;
+        }
+
+        // Most hit points first, keeping each which drains slower than all before it
+        own.sort_by(|a, b| {
+            b.stats
+                .hit_points
+                .partial_cmp(&a.stats.hit_points)
+                .unwrap()
+                .then(a.actual_dps.partial_cmp(&b.actual_dps).unwrap())
+        });
+        let mut slowest = f64::INFINITY;
+        for point in own {
+            if point.actual_dps < slowest {
+                slowest = point.actual_dps;
+                points.push(point);
+            }
+        }
+    }
+
+    points
+}
+
+/// Is `a` better than `b`, by time to kill where there's a hull and the
+/// objective is survival?
+fn better(scenario: &Scenario, a: &TestResult, b: &TestResult) -> bool {
+    match (a.time_to_kill, b.time_to_kill) {
+        (Some(a_ttk), Some(b_ttk))
+            if scenario.objective == Objective::Survival
+                && a.actual_dps > 0.0
+                && b.actual_dps > 0.0 =>
+        {
+            a_ttk > b_ttk
+        }
+        _ => compare_results(scenario, a, b) == std::cmp::Ordering::Less,
+    }
+}
+
+/// Every way to fill `slots` (sorted largest first) with the kinds of module,
+/// treating slots of the same size as interchangeable.
+fn assignments(slots: &[u8], fill: &mut Vec<Kind>, task: &mut dyn FnMut(&[Kind])) {
+    let depth = fill.len();
+    if depth == slots.len() {
+        task(fill);
+        return;
+    }
+
+    for &kind in KINDS.iter() {
+        if depth > 0 && slots[depth] == slots[depth - 1] && kind < fill[depth - 1] {
+            continue;
+        }
+        fill.push(kind);
+        assignments(slots, fill, task);
+        fill.pop();
+    }
+}
+
+/// The best loadout from `front` with the scenario's internals fitted.
+fn score(
+    scenario: &Scenario,
+    db: &Database,
+    front: &[Point],
+    boosters: &BoosterFront,
+    reinforced_key: f64,
+) -> Result<Option<TestResult>, Error> {
+    let cell_banks = db.resolve_cell_banks(&scenario.cell_banks)?;
+    let hull = db.resolve_hull(scenario)?;
+    let cell_power = calculate_cell_power(&cell_banks);
+    let power_budget = scenario.power_budget();
+
+    let mut best: Option<TestResult> = None;
+    for point in front.iter() {
+        let mut stats = point.stats.clone();
+        stats.hit_points += scenario.reinforced_mj - reinforced_key;
+        stats.power_draw += cell_power;
+
+        if power_budget
+            .map(|budget| stats.power_draw > budget)
+            .unwrap_or(false)
+        {
+            continue;
+        }
+
+        let survival_time = (stats.hit_points + calculate_cell_mj(scenario, &cell_banks, &stats))
+            / point.actual_dps;
+
+        if best
+            .as_ref()
+            .map(|b| beats(scenario, point.actual_dps, survival_time, &stats, b))
+            .unwrap_or(true)
+        {
+            best = Some(TestResult {
+                actual_dps: point.actual_dps,
+                survival_time,
+                time_to_kill: hull
+                    .as_ref()
+                    .map(|hull| hull::calculate_time_to_kill(survival_time, hull)),
+                shield: point.shield.clone(),
+                boosters: boosters.selections[point.booster_id]
+                    .iter()
+                    .cloned()
+                    .cloned()
+                    .collect(),
+                stats,
+            });
+        }
+    }
+
+    Ok(best)
+}
+
+/// The generator class for a slot: the largest the slot and ship take, or
+/// with an inventory, the largest owned which fits.
+fn slot_class(scenario: &Scenario, largest: u8, size: u8) -> u8 {
+    let class = size.min(largest);
+    scenario
+        .inventory
+        .as_ref()
+        .and_then(|inventory| {
+            inventory
+                .generator_classes()
+                .into_iter()
+                .filter(|&c| c <= class)
+                .max()
+        })
+        .unwrap_or(class)
+}
+
+/// Find the best use of internal slots of the given sizes, or None if no
+/// loadout meets the scenario's regen time limit, constraints and power budget.
+pub fn fit_internals(
+    scenario: &Scenario,
+    db: &Database,
+    slots: &[u8],
+) -> Result<Option<Fitting>, Error> {
+    let base = clear_internals(scenario);
+    let has_hull = base.bulkheads.is_some();
+
+    let largest = Candidates::select(&base, db, true)?;
+    let (filtered_pairs, _) = filter_booster_pairs(&largest.boosters, !base.prefilter());
+    let boosters = booster_front(
+        &base,
+        &largest.boosters,
+        &largest.pinned,
+        &filtered_pairs,
+        base.prefilter(),
+    );
+
+    // Generators for each class a slot can hold, reporting the largest slot
+    // if none can
+    let mut generators: HashMap<u8, Vec<ShieldGenerator>> = HashMap::new();
+    let mut too_small = None;
+    for &size in slots.iter().sorted().rev() {
+        let class = slot_class(&base, largest.shield_class, size);
+        if generators.contains_key(&class) {
+            continue;
+        }
+        let scenario = Scenario {
+            shield_class: Some(class),
+            ..base.clone()
+        };
+        match Candidates::select(&scenario, db, true) {
+            Ok(candidates) => {
+                generators.insert(class, candidates.generators);
+            }
+            Err(e @ Error::InvalidShieldClass { .. }) | Err(e @ Error::NoCandidates(_)) => {
+                too_small.get_or_insert(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    if let (true, Some(e)) = (generators.is_empty(), too_small) {
+        return Err(e);
+    }
+
+    let mut fronts: HashMap<(u8, u64), Vec<Point>> = HashMap::new();
+    let mut best: Option<Fitting> = None;
+
+    for (shield_slot, &size) in slots.iter().enumerate() {
+        let class = slot_class(&base, largest.shield_class, size);
+        if !generators.contains_key(&class) || slots[..shield_slot].contains(&size) {
+            continue;
+        }
+
+        let mut rest: Vec<(usize, u8)> = slots
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(i, _)| i != shield_slot)
+            .collect();
+        rest.sort_by_key(|&(_, size)| std::cmp::Reverse(size));
+        let sizes: Vec<u8> = rest.iter().map(|&(_, size)| size).collect();
+
+        let mut error = None;
+        assignments(&sizes, &mut vec![], &mut |kinds| {
+            if error.is_some() {
+                return;
+            }
+
+            let mut fitted: Vec<(u8, SlotModule)> = slots
+                .iter()
+                .map(|&size| (size, SlotModule::Empty))
+                .collect();
+            fitted[shield_slot].1 = SlotModule::ShieldGenerator(class);
+            for (&(slot, size), kind) in rest.iter().zip(kinds) {
+                fitted[slot].1 = match kind {
+                    Kind::CellBank => SlotModule::CellBank(CellBankSpec {
+                        class: size.min(8),
+                        rating: 'A',
+                        engineering: "None".to_string(),
+                        experimental: "No Experimental Effect".to_string(),
+                    }),
+                    Kind::Guardian => db
+                        .guardian_reinforcements
+                        .iter()
+                        .find(|gsrp| gsrp.class == size.min(5) && gsrp.rating == 'D')
+                        .map_or(SlotModule::Empty, |gsrp| {
+                            SlotModule::GuardianReinforcement(gsrp.clone())
+                        }),
+                    Kind::Other if has_hull => {
+                        SlotModule::HullReinforcement(HullReinforcementSpec {
+                            class: size.min(5),
+                            rating: 'D',
+                        })
+                    }
+                    Kind::Other => SlotModule::Empty,
+                };
+            }
+
+            let scenario = with_internals(&base, &fitted);
+            let reinforced_key = if base.regen_time_limit.is_some()
+                || objective::has_static_score(&base)
+                || !constraint::constraints(&base).is_empty()
+            {
+                scenario.reinforced_mj
+            } else {
+                0.0
+            };
+            let front = fronts
+                .entry((class, reinforced_key.to_bits()))
+                .or_insert_with(|| {
+                    loadout_front(&base, &generators[&class], &boosters, reinforced_key)
+                });
+
+            match score(&scenario, db, front, &boosters, reinforced_key) {
+                Ok(Some(result)) => {
+                    if best
+                        .as_ref()
+                        .map(|b| better(&base, &result, &b.result))
+                        .unwrap_or(true)
+                    {
+                        best = Some(Fitting {
+                            slots: fitted,
+                            result,
+                        });
+                    }
+                }
+                Ok(None) => (),
+                Err(e) => error = Some(e),
+            }
+        });
+
+        if let Some(e) = error {
+            return Err(e);
+        }
+    }
+
+    Ok(best)
+}
+
+#[test]
+fn test_fit_internals() {
+    let db = Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        shield_booster_count: 2,
+        kinetic_dps: 100.0,
+        thermal_dps: 50.0,
+        bulkheads: Some("Military".to_string()),
+        ..Scenario::default()
+    };
+
+    let fitting = fit_internals(&scenario, &db, &[6, 5, 3, 2])
+        .unwrap()
+        .unwrap();
+    let generators = fitting
+        .slots
+        .iter()
+        .filter(|(_, m)| matches!(m, SlotModule::ShieldGenerator(_)))
+        .count();
+    assert_eq!(generators, 1);
+
+    // Searching the fitted scenario directly finds the same loadout
+    let fitted = fitting.apply(&scenario);
+    let best = crate::optimise(&fitted, &db).unwrap().results.remove(0);
+    assert!((best.survival_time - fitting.result.survival_time).abs() < 1e-9);
+    assert!((best.time_to_kill.unwrap() - fitting.result.time_to_kill.unwrap()).abs() < 1e-9);
+
+    // Every slot beats the default of leaving internals out
+    let bare = crate::optimise(&scenario, &db).unwrap().results.remove(0);
+    assert!(fitting.result.time_to_kill.unwrap() > bare.time_to_kill.unwrap());
+    assert!(fitting
+        .slots
+        .iter()
+        .all(|(_, m)| !matches!(m, SlotModule::Empty)));
+
+    assert!(fit_internals(&scenario, &db, &[2, 1]).is_err());
+}
@@ -0,0 +1,190 @@
+/*
+Shield strength from hull mass.
+
+`data/Shields.csv` holds pre-computed generators for every ship, but the
+strength of a shield depends on the mass of the hull it protects, so those
+figures only hold for each ship's stock hull mass.  This derives the same rows
+from first principles instead.
+
+Each generator type and class (`data/ShieldGeneratorModules.csv`) has a
+minimum, optimal and maximum hull mass, and a strength multiplier at each of
+them.  A hull at or below the minimum mass gets the maximum multiplier, and
+generators can't be fitted at all to hulls above the maximum mass.  In between
+the multiplier follows a power curve through the optimal point:
+
+    norm = (max_mass - hull_mass) / (max_mass - min_mass)
+    power = ln((opt_mul - min_mul) / (max_mul - min_mul))
+          / ln((max_mass - opt_mass) / (max_mass - min_mass))
+    multiplier = min_mul + norm^power * (max_mul - min_mul)
+
+Shield strength is then the ship's base shield times the multiplier, with
+blueprint and experimental effect modifiers applied on top.
+*/
+
+use serde::Deserialize;
+
+use crate::blueprint::{
+    calculate_modifiers, variants, BlueprintModifier, ExperimentalModifier, Grade, Modifiers,
+    ModuleKind,
+};
+use crate::hull::Ship;
+use crate::{Error, ShieldGenerator};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ShieldGeneratorModule {
+    #[serde(rename = "Type")]
+    pub kind: String,
+    pub class: u8,
+    pub min_mass: f64,
+    pub opt_mass: f64,
+    pub max_mass: f64,
+    pub min_mul: f64,
+    pub opt_mul: f64,
+    pub max_mul: f64,
+    pub regen_rate: f64,
+    /// Unengineered resonances
+    pub exp_res: f64,
+    pub kin_res: f64,
+    pub therm_res: f64,
+    /// MW drawn while powered
+    pub power_draw: f64,
+}
+
+/// Strength multiplier a generator gives a hull of the given mass.
+pub fn calculate_shield_multiplier(module: &ShieldGeneratorModule, hull_mass: f64) -> f64 {
+    let range = module.max_mass - module.min_mass;
+    let norm = ((module.max_mass - hull_mass) / range).min(1.0);
+    let power = ((module.opt_mul - module.min_mul) / (module.max_mul - module.min_mul)).ln()
+        / ((module.max_mass - module.opt_mass) / range).min(1.0).ln();
+
+    module.min_mul + norm.powf(power) * (module.max_mul - module.min_mul)
+}
+
+/// Every generator engineered to the given grade which can be fitted to the
+/// ship at the given hull mass.
+pub fn derive_generators(
+    ship: &Ship,
+    hull_mass: f64,
+    grade: Grade,
+    modules: &[ShieldGeneratorModule],
+    blueprints: &[BlueprintModifier],
+    experimentals: &[ExperimentalModifier],
+) -> Result<Vec<ShieldGenerator>, Error> {
+    let engineering = variants(ModuleKind::Generator, blueprints, experimentals)
+        .into_iter()
+        .map(|(blueprint, experimental)| {
+            let modifiers = calculate_modifiers(
+                ModuleKind::Generator,
+                &blueprint,
+                grade,
+                &experimental,
+                blueprints,
+                experimentals,
+            )?;
+            Ok((blueprint, experimental, modifiers))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let mut generators = vec![];
+
+    for module in modules
+        .iter()
+        .filter(|m| m.class <= ship.max_shield_class && hull_mass <= m.max_mass)
+    {
+        for (blueprint, experimental, modifiers) in engineering.iter() {
+            generators.push(engineer_generator(
+                ship,
+                module,
+                hull_mass,
+                blueprint,
+                experimental,
+                grade,
+                modifiers,
+            ));
+        }
+    }
+
+    Ok(generators)
+}
+
+/// A generator of the given module for the ship at the given hull mass, with
+/// a blueprint's modifiers applied.
+pub fn engineer_generator(
+    ship: &Ship,
+    module: &ShieldGeneratorModule,
+    hull_mass: f64,
+    blueprint: &str,
+    experimental: &str,
+    grade: Grade,
+    modifiers: &Modifiers,
+) -> ShieldGenerator {
+    let strength = ship.base_shield * calculate_shield_multiplier(module, hull_mass);
+
+    ShieldGenerator {
+        ship: ship.ship.clone(),
+        class: module.class,
+        kind: module.kind.clone(),
+        engineering: blueprint.to_string(),
+        experimental: experimental.to_string(),
+        grade,
+        shield_strength: strength * modifiers.shield_strength,
+        regen_rate: module.regen_rate * modifiers.regen_rate,
+        exp_res: module.exp_res * modifiers.exp_res,
+        kin_res: module.kin_res * modifiers.kin_res,
+        therm_res: module.therm_res * modifiers.therm_res,
+        caus_res: 1.0,
+        power_draw: module.power_draw,
+    }
+}
+
+#[test]
+fn test_derive_generators() {
+    let db = crate::Database::builtin().unwrap();
+    let derive = |ship: &Ship, mass: f64| {
+        derive_generators(
+            ship,
+            mass,
+            Grade::MAX,
+            &db.generator_modules,
+            &db.blueprint_modifiers,
+            &db.experimental_modifiers,
+        )
+        .unwrap()
+    };
+
+    // The stock hull mass reproduces the pre-computed table
+    for name in ["Anaconda", "Federal Corvette", "Sidewinder"].iter() {
+        let ship = db.ships.iter().find(|s| s.ship == *name).unwrap();
+        let derived = derive(ship, ship.hull_mass);
+        let table: Vec<&ShieldGenerator> =
+            db.generators.iter().filter(|g| g.ship == *name).collect();
+        assert_eq!(derived.len(), table.len());
+
+        for gen in table {
+            let d = derived
+                .iter()
+                .find(|d| {
+                    d.class == gen.class
+                        && d.kind == gen.kind
+                        && d.engineering == gen.engineering
+                        && d.experimental == gen.experimental
+                })
+                .unwrap();
+            assert!((d.shield_strength - gen.shield_strength).abs() < 0.05);
+            assert!((d.regen_rate - gen.regen_rate).abs() < 1e-9);
+            assert!((d.exp_res - gen.exp_res).abs() < 1e-3);
+            assert!((d.kin_res - gen.kin_res).abs() < 1e-3);
+            assert!((d.therm_res - gen.therm_res).abs() < 1e-3);
+        }
+    }
+
+    // A heavier hull gets a weaker shield, and loses the smallest classes
+    let ship = db.ships.iter().find(|s| s.ship == "Python").unwrap();
+    let light = derive(ship, 350.0);
+    let heavy = derive(ship, 500.0);
+    assert!(heavy.len() < light.len());
+    let best =
+        |gens: &[ShieldGenerator]| gens.iter().map(|g| g.shield_strength).fold(0.0, f64::max);
+    assert!(best(&heavy) < best(&light));
+}
@@ -0,0 +1,274 @@
+/*
+Hull survivability.
+
+Once the shield falls the attacker works on the hull, so total time to kill
+is shield survival plus hull survival against the same damage profile.
+
+Hull armour is the ship's base armour boosted by its bulkheads, plus the flat
+armour of any Hull Reinforcement Packages.  Resistances come from the
+bulkheads, with package resistances stacking the same way booster resistances
+do, diminishing past 30%.  Absolute and caustic damage ignore resistances.
+Weapons with less armour piercing than the hull's hardness do proportionally
+less damage, absolute included, but caustic damage is corrosion eating at the
+hull rather than weapon hits, so hardness doesn't stop it.
+*/
+
+use std::io::Read;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{diminish_res, parse_csv, Error, Scenario};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Ship {
+    pub ship: String,
+    pub armour: f64,
+    pub hardness: f64,
+    /// Shield strength before the generator's mass multiplier
+    pub base_shield: f64,
+    /// Stock hull mass in tonnes
+    pub hull_mass: f64,
+    pub max_shield_class: u8,
+    /// Ship identifier used by the game's journal, e.g. `federation_corvette`
+    pub symbol: String,
+}
+
+/// Bulkheads, with resistances converted to resonances on load.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Bulkhead {
+    #[serde(rename = "Type")]
+    pub kind: String,
+    pub hull_boost: f64,
+    pub exp_res: f64,
+    pub kin_res: f64,
+    pub therm_res: f64,
+}
+
+/// Hull Reinforcement Packages, with resistances converted to resonances on load.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HullReinforcement {
+    pub class: u8,
+    pub rating: char,
+    pub armour: f64,
+    pub exp_res: f64,
+    pub kin_res: f64,
+    pub therm_res: f64,
+}
+
+/// A Hull Reinforcement Package size, written as `<class><rating>`, e.g. `5D`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HullReinforcementSpec {
+    pub class: u8,
+    pub rating: char,
+}
+
+impl FromStr for HullReinforcementSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::UnknownHullReinforcement(s.to_string());
+        let s = s.trim();
+        let rating = s.chars().last().ok_or_else(err)?.to_ascii_uppercase();
+        let class = s[..s.len() - rating.len_utf8()]
+            .parse()
+            .map_err(|_| err())?;
+
+        Ok(Self { class, rating })
+    }
+}
+
+pub(crate) fn load_bulkheads<R: Read>(bulkheads: R) -> Result<Vec<Bulkhead>, csv::Error> {
+    Ok(parse_csv::<Bulkhead, _>(bulkheads)?
+        .into_iter()
+        .map(|mut bulkhead| {
+            bulkhead.exp_res = 1.0 - bulkhead.exp_res;
+            bulkhead.kin_res = 1.0 - bulkhead.kin_res;
+            bulkhead.therm_res = 1.0 - bulkhead.therm_res;
+            bulkhead
+        })
+        .collect())
+}
+
+pub(crate) fn load_hull_reinforcements<R: Read>(
+    reinforcements: R,
+) -> Result<Vec<HullReinforcement>, csv::Error> {
+    Ok(parse_csv::<HullReinforcement, _>(reinforcements)?
+        .into_iter()
+        .map(|mut hrp| {
+            hrp.exp_res = 1.0 - hrp.exp_res;
+            hrp.kin_res = 1.0 - hrp.kin_res;
+            hrp.therm_res = 1.0 - hrp.therm_res;
+            hrp
+        })
+        .collect())
+}
+
+/// Effective stats of a ship's hull against the scenario's attacker.
+#[derive(Debug, Clone, Serialize)]
+pub struct HullStat {
+    pub bulkhead: String,
+    pub armour: f64,
+    pub hardness: f64,
+    pub exp_res: f64,
+    pub kin_res: f64,
+    pub therm_res: f64,
+    /// Hull damage per second once the shield is down
+    pub actual_dps: f64,
+    pub survival_time: f64,
+}
+
+impl HullStat {
+    /// Work out the hull of the scenario's ship, if bulkheads or hull
+    /// reinforcements were given.  Reinforcements alone assume Lightweight Alloy.
+    pub fn resolve(
+        test: &Scenario,
+        ships: &[Ship],
+        bulkheads: &[Bulkhead],
+        reinforcements: &[HullReinforcement],
+    ) -> Result<Option<Self>, Error> {
+        if test.bulkheads.is_none() && test.hull_reinforcements.is_empty() {
+            return Ok(None);
+        }
+
+        let ship = ships
+            .iter()
+            .find(|ship| ship.ship == test.ship)
+            .ok_or_else(|| Error::UnknownShip(test.ship.clone()))?;
+
+        let name = test.bulkheads.as_deref().unwrap_or("Lightweight");
+        let bulkhead = bulkheads
+            .iter()
+            .find(|b| {
+                b.kind.eq_ignore_ascii_case(name)
+                    || b.kind
+                        .split_whitespace()
+                        .next()
+                        .map(|word| word.eq_ignore_ascii_case(name))
+                        .unwrap_or(false)
+            })
+            .ok_or_else(|| Error::UnknownBulkhead(name.to_string()))?;
+
+        let fitted = test
+            .hull_reinforcements
+            .iter()
+            .map(|spec| {
+                reinforcements
+                    .iter()
+                    .find(|hrp| hrp.class == spec.class && hrp.rating == spec.rating)
+                    .ok_or_else(|| {
+                        Error::UnknownHullReinforcement(format!("{}{}", spec.class, spec.rating))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut hull = calculate_hull_stats(ship, bulkhead, &fitted);
+        hull.actual_dps = calculate_hull_dps(test, &hull);
+        hull.survival_time = hull.armour / hull.actual_dps;
+
+        Ok(Some(hull))
+    }
+}
+
+pub fn calculate_hull_stats(
+    ship: &Ship,
+    bulkhead: &Bulkhead,
+    reinforcements: &[&HullReinforcement],
+) -> HullStat {
+    let mut exp_modifier = 1.0;
+    let mut kin_modifier = 1.0;
+    let mut therm_modifier = 1.0;
+    let mut armour = ship.armour * (1.0 + bulkhead.hull_boost);
+
+    for hrp in reinforcements.iter() {
+        exp_modifier *= hrp.exp_res;
+        kin_modifier *= hrp.kin_res;
+        therm_modifier *= hrp.therm_res;
+        armour += hrp.armour;
+    }
+
+    HullStat {
+        bulkhead: bulkhead.kind.clone(),
+        armour,
+        hardness: ship.hardness,
+        exp_res: bulkhead.exp_res * diminish_res(exp_modifier),
+        kin_res: bulkhead.kin_res * diminish_res(kin_modifier),
+        therm_res: bulkhead.therm_res * diminish_res(therm_modifier),
+        actual_dps: 0.0,
+        survival_time: 0.0,
+    }
+}
+
+/// Damage per second the hull takes from the scenario's attacker.
+pub fn calculate_hull_dps(test: &Scenario, hull: &HullStat) -> f64 {
+    let piercing = test
+        .armour_piercing
+        .map(|ap| (ap / hull.hardness).min(1.0))
+        .unwrap_or(1.0);
+
+    let [exp_eff, kin_eff, therm_eff, abs_eff, caus_eff] = test.effectiveness();
+
+    piercing
+        * (exp_eff * test.explosive_dps * hull.exp_res
+            + kin_eff * test.kinetic_dps * hull.kin_res
+            + therm_eff * test.thermal_dps * hull.therm_res
+            + abs_eff * test.absolute_dps)
+        + caus_eff * test.caustic_dps
+}
+
+/// Total time to destroy a ship, given how long its shield lasts.
+pub fn calculate_time_to_kill(shield_survival: f64, hull: &HullStat) -> f64 {
+    if shield_survival < 0.0 {
+        // Regen outpaces the attacker, the shield never falls
+        shield_survival
+    } else {
+        shield_survival + hull.survival_time
+    }
+}
+
+#[test]
+fn test_hull_stats() {
+    let ship = Ship {
+        ship: "Test".to_string(),
+        armour: 100.0,
+        hardness: 50.0,
+        base_shield: 100.0,
+        hull_mass: 100.0,
+        max_shield_class: 4,
+        symbol: "test".to_string(),
+    };
+    let bulkhead = Bulkhead {
+        kind: "Test".to_string(),
+        hull_boost: 1.5,
+        exp_res: 1.4,
+        kin_res: 1.2,
+        therm_res: 1.0,
+    };
+    let hrp = HullReinforcement {
+        class: 5,
+        rating: 'D',
+        armour: 100.0,
+        exp_res: 0.5,
+        kin_res: 1.0,
+        therm_res: 1.0,
+    };
+
+    let hull = calculate_hull_stats(&ship, &bulkhead, &[&hrp]);
+    assert!((hull.armour - 350.0).abs() < 1e-9);
+    // 50% resistance diminishes to 40%
+    assert!((hull.exp_res - 1.4 * 0.6).abs() < 1e-9);
+
+    let test = Scenario {
+        kinetic_dps: 100.0,
+        absolute_dps: 10.0,
+        caustic_dps: 4.0,
+        damage_effectiveness: 1.0,
+        armour_piercing: Some(25.0),
+        ..Scenario::default()
+    };
+    // Half the kinetic and absolute damage gets through, and all the caustic
+    assert!((calculate_hull_dps(&test, &hull) - 69.0).abs() < 1e-9);
+}
@@ -0,0 +1,458 @@
+/*
+Searching only over modules a commander owns.
+
+An inventory lists the shield generators and boosters already owned, so the
+search can find the best loadout possible right now, without engineering
+anything.  Each owned booster is fitted at most as many times as it's owned.
+The inventory can be read from a CSV file with the columns
+
+  Type          Normal, Bi-Weave, Prismatic or Booster
+  Class         generator class, such as 6, or booster rating, such as A
+  Engineering   blueprint, or None
+  Experimental  experimental effect, blank for none
+  Grade         blueprint grade, blank for 5
+  Quality       how well that grade rolled from 0 to 1, blank for 1
+  Count         how many are owned
+
+or from the journal, taking the modules in storage from the latest
+`StoredModules` event, along with the shield modules fitted in the latest
+`Loadout`.  The journal doesn't say which experimental effect a stored module
+carries, so stored modules are taken to have none.
+
+Owned modules are engineered from the blueprint tables at their grade and
+quality, rather than taking the figures the game rolled.
+*/
+
+use std::fs::File;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::blueprint::{calculate_modifiers, Grade, Modifiers, ModuleKind};
+use crate::generator::engineer_generator;
+use crate::journal::{self, LoadoutEvent};
+use crate::{engineer_booster, parse_csv, Database, Error, ShieldBooster, ShieldGenerator};
+
+/// Some number of one module, as a row of an inventory file.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct OwnedModule {
+    /// Generator type, or `Booster`
+    #[serde(rename = "Type")]
+    pub kind: String,
+    /// Generator class or booster rating
+    pub class: String,
+    pub engineering: String,
+    pub experimental: Option<String>,
+    pub grade: Option<u8>,
+    pub quality: Option<f64>,
+    pub count: usize,
+}
+
+impl OwnedModule {
+    pub fn is_booster(&self) -> bool {
+        self.kind.eq_ignore_ascii_case("Booster")
+    }
+
+    fn is_engineered(&self) -> bool {
+        !self.engineering.eq_ignore_ascii_case("None")
+    }
+
+    fn experimental(&self) -> &str {
+        match self.experimental {
+            Some(ref experimental) if self.is_engineered() => experimental,
+            _ => "No Experimental Effect",
+        }
+    }
+
+    fn grade(&self) -> Result<Grade, Error> {
+        if self.is_engineered() {
+            Grade::new(self.grade.unwrap_or(5), self.quality.unwrap_or(1.0))
+        } else {
+            Ok(Grade::MAX)
+        }
+    }
+
+    fn rating(&self) -> Option<char> {
+        let mut chars = self.class.trim().chars();
+        match (chars.next(), chars.next()) {
+            (Some(rating), None) => Some(rating.to_ascii_uppercase()),
+            _ => None,
+        }
+    }
+
+    fn unknown(&self) -> String {
+        format!(
+            "{} {},{},{}",
+            self.class,
+            self.kind,
+            self.engineering,
+            self.experimental()
+        )
+    }
+
+    /// Is this the given booster?
+    pub fn matches(&self, booster: &ShieldBooster) -> bool {
+        self.is_booster()
+            && self.rating() == Some(booster.rating)
+            && self.engineering.eq_ignore_ascii_case(&booster.engineering)
+            && self
+                .experimental()
+                .eq_ignore_ascii_case(&booster.experimental)
+            && self.grade().map(|g| g == booster.grade).unwrap_or(false)
+    }
+
+    /// The blueprint and experimental effect as the tables name them, and
+    /// their modifiers.
+    fn engineering(
+        &self,
+        module: ModuleKind,
+        db: &Database,
+    ) -> Result<(String, String, Modifiers), Error> {
+        if !self.is_engineered() {
+            return Ok((
+                "None".to_string(),
+                "No Experimental Effect".to_string(),
+                Modifiers::default(),
+            ));
+        }
+
+        let blueprint = db
+            .blueprint_modifiers
+            .iter()
+            .find(|m| m.module == module && m.blueprint.eq_ignore_ascii_case(&self.engineering))
+            .map(|m| m.blueprint.clone())
+            .ok_or_else(|| Error::UnknownBlueprint(self.engineering.clone()))?;
+        let experimental = db
+            .experimental_modifiers
+            .iter()
+            .find(|m| {
+                m.module == module && m.experimental.eq_ignore_ascii_case(self.experimental())
+            })
+            .map(|m| m.experimental.clone())
+            .unwrap_or_else(|| self.experimental().to_string());
+        let modifiers = calculate_modifiers(
+            module,
+            &blueprint,
+            self.grade()?,
+            &experimental,
+            &db.blueprint_modifiers,
+            &db.experimental_modifiers,
+        )?;
+
+        Ok((blueprint, experimental, modifiers))
+    }
+
+    /// The module described by a journal item name and engineering, if it's a
+    /// shield generator or booster.
+    fn from_journal(
+        item: &str,
+        blueprint: Option<&str>,
+        experimental: Option<&str>,
+        level: Option<u8>,
+        quality: Option<f64>,
+    ) -> Result<Option<Self>, Error> {
+        let (kind, class) = match journal::generator_item(item)? {
+            Some((class, kind)) => (kind.to_string(), class.to_string()),
+            None => match journal::booster_item(item) {
+                Some(rating) => ("Booster".to_string(), rating.to_string()),
+                None => return Ok(None),
+            },
+        };
+
+        Ok(Some(Self {
+            kind,
+            class,
+            engineering: blueprint
+                .map(journal::blueprint_name)
+                .unwrap_or_else(|| "None".to_string()),
+            experimental: experimental.map(str::to_string),
+            grade: level,
+            quality,
+            count: 1,
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StoredModulesEvent {
+    items: Vec<StoredItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StoredItem {
+    name: String,
+    engineer_modifications: Option<String>,
+    level: Option<u8>,
+    quality: Option<f64>,
+}
+
+/// Every shield generator and booster a commander owns.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Inventory {
+    pub modules: Vec<OwnedModule>,
+}
+
+impl Inventory {
+    /// Read an inventory CSV file.
+    pub fn read_csv(path: &Path) -> Result<Self, Error> {
+        let mut inventory = Self::default();
+        for module in parse_csv(File::open(path)?)? {
+            inventory.add(module);
+        }
+        Ok(inventory)
+    }
+
+    /// Read the modules in storage and the shield modules fitted from a
+    /// journal directory.
+    pub fn read_journal(dir: &Path) -> Result<Self, Error> {
+        let stored = journal::latest_event(dir, "StoredModules")?
+            .ok_or_else(|| Error::NoStoredModules(dir.display().to_string()))?;
+        let stored: StoredModulesEvent = serde_json::from_str(&stored)?;
+        let fitted = match journal::latest_event(dir, "Loadout")? {
+            Some(line) => Some(serde_json::from_str(&line)?),
+            None => None,
+        };
+
+        Self::from_events(&stored, fitted.as_ref())
+    }
+
+    fn from_events(
+        stored: &StoredModulesEvent,
+        fitted: Option<&LoadoutEvent>,
+    ) -> Result<Self, Error> {
+        let mut inventory = Self::default();
+
+        for item in stored.items.iter() {
+            // Stored item names are localisation keys, such as
+            // `$int_shieldgenerator_size6_class5_name;`
+            let name = item.name.trim_start_matches('$').trim_end_matches(';');
+            let name = name.strip_suffix("_name").unwrap_or(name);
+            let blueprint = item.engineer_modifications.as_deref();
+            if let Some(module) =
+                OwnedModule::from_journal(name, blueprint, None, item.level, item.quality)?
+            {
+                inventory.add(module);
+            }
+        }
+
+        for module in fitted.iter().flat_map(|event| event.modules.iter()) {
+            let engineering = module.engineering.as_ref();
+            if let Some(module) = OwnedModule::from_journal(
+                &module.item,
+                engineering.map(|e| &e.blueprint_name[..]),
+                engineering.and_then(|e| e.experimental_effect.as_deref()),
+                engineering.map(|e| e.level),
+                engineering.map(|e| e.quality),
+            )? {
+                inventory.add(module);
+            }
+        }
+
+        Ok(inventory)
+    }
+
+    /// Add some owned modules, counting them alongside any the same.
+    pub fn add(&mut self, module: OwnedModule) {
+        let uncounted = |m: &OwnedModule| OwnedModule {
+            count: 0,
+            ..m.clone()
+        };
+        let same = self
+            .modules
+            .iter_mut()
+            .find(|m| uncounted(m) == uncounted(&module));
+        match same {
+            Some(owned) => owned.count += module.count,
+            None => self.modules.push(module),
+        }
+    }
+
+    /// How many of a booster are owned.
+    pub fn booster_count(&self, booster: &ShieldBooster) -> usize {
+        self.modules
+            .iter()
+            .filter(|m| m.matches(booster))
+            .map(|m| m.count)
+            .sum()
+    }
+
+    /// Classes of the generators owned.
+    pub fn generator_classes(&self) -> Vec<u8> {
+        self.modules
+            .iter()
+            .filter(|m| !m.is_booster())
+            .filter_map(|m| m.class.trim().parse().ok())
+            .collect()
+    }
+
+    /// Each owned booster.
+    pub fn boosters(&self, db: &Database) -> Result<Vec<ShieldBooster>, Error> {
+        let mut boosters: Vec<ShieldBooster> = vec![];
+        for owned in self
+            .modules
+            .iter()
+            .filter(|m| m.is_booster() && m.count > 0)
+        {
+            let module = db
+                .booster_modules
+                .iter()
+                .find(|m| Some(m.rating) == owned.rating())
+                .ok_or_else(|| Error::UnknownBooster(owned.unknown()))?;
+            let (blueprint, experimental, modifiers) =
+                owned.engineering(ModuleKind::Booster, db)?;
+            let booster = engineer_booster(
+                module,
+                &blueprint,
+                &experimental,
+                owned.grade()?,
+                &modifiers,
+            );
+            if !boosters.contains(&booster) {
+                boosters.push(booster);
+            }
+        }
+        Ok(boosters)
+    }
+
+    /// Each owned generator which can be fitted to the ship at the given hull
+    /// mass, or its stock mass.
+    pub fn generators(
+        &self,
+        db: &Database,
+        ship: &str,
+        hull_mass: Option<f64>,
+    ) -> Result<Vec<ShieldGenerator>, Error> {
+        let ship = db
+            .ships
+            .iter()
+            .find(|s| s.ship == ship)
+            .ok_or_else(|| Error::UnknownShip(ship.to_string()))?;
+        let hull_mass = hull_mass.unwrap_or(ship.hull_mass);
+
+        let mut generators = vec![];
+        for owned in self
+            .modules
+            .iter()
+            .filter(|m| !m.is_booster() && m.count > 0)
+        {
+            let module = db
+                .generator_modules
+                .iter()
+                .find(|m| {
+                    m.kind.eq_ignore_ascii_case(&owned.kind)
+                        && owned.class.trim().parse() == Ok(m.class)
+                })
+                .ok_or_else(|| Error::UnknownShield(owned.unknown()))?;
+            if module.class > ship.max_shield_class || hull_mass > module.max_mass {
+                continue;
+            }
+
+            let (blueprint, experimental, modifiers) =
+                owned.engineering(ModuleKind::Generator, db)?;
+            let generator = engineer_generator(
+                ship,
+                module,
+                hull_mass,
+                &blueprint,
+                &experimental,
+                owned.grade()?,
+                &modifiers,
+            );
+            generators.push(generator);
+        }
+        Ok(generators)
+    }
+}
+
+#[test]
+fn test_inventory() {
+    let db = Database::builtin().unwrap();
+    let inventory = concat!(
+        "Type,Class,Engineering,Experimental,Grade,Quality,Count\n",
+        "Prismatic,6,Reinforced,Hi-Cap,5,1,1\n",
+        "Bi-Weave,6,Thermal resistant,,3,0.5,1\n",
+        "Booster,A,Heavy Duty,Super Capacitors,5,1,1\n",
+        "Booster,A,Resistance Augmented,Thermo Block,5,1,1\n",
+        "Booster,A,None,,,,3\n",
+    );
+    let mut inventory = Inventory {
+        modules: parse_csv(inventory.as_bytes()).unwrap(),
+    };
+    inventory.add(inventory.modules[2].clone());
+
+    let scenario = crate::Scenario {
+        ship: "Python".to_string(),
+        shield_booster_count: 4,
+        thermal_dps: 100.0,
+        inventory: Some(inventory.clone()),
+        ..crate::Scenario::default()
+    };
+    let best = crate::optimise(&scenario, &db).unwrap().results.remove(0);
+    assert!(best.boosters.iter().all(|b| inventory.booster_count(b) > 0));
+    assert_eq!(
+        best.boosters
+            .iter()
+            .filter(|b| b.engineering == "Heavy Duty")
+            .count(),
+        2
+    );
+
+    // Owning a module from the tables gives its figures
+    let table = db
+        .ship_generators("Python", None, Grade::MAX)
+        .unwrap()
+        .into_iter()
+        .find(|g| {
+            g.class == 6
+                && g.kind == "Prismatic"
+                && g.engineering == "Reinforced"
+                && g.experimental == "Hi-Cap"
+        })
+        .unwrap();
+    let owned = inventory.generators(&db, "Python", None).unwrap();
+    assert!((owned[0].shield_strength - table.shield_strength).abs() < 0.05);
+
+    // Too few owned to fill the slots
+    let short = crate::Scenario {
+        exclude_blueprints: vec!["None".to_string()],
+        ..scenario
+    };
+    assert!(crate::optimise(&short, &db).is_err());
+
+    let stored = StoredModulesEvent {
+        items: vec![
+            StoredItem {
+                name: "$hpt_shieldbooster_size0_class5_name;".to_string(),
+                engineer_modifications: Some("ShieldBooster_HeavyDuty".to_string()),
+                level: Some(4),
+                quality: Some(0.5),
+            },
+            StoredItem {
+                name: "$int_hullreinforcement_size5_class2_name;".to_string(),
+                engineer_modifications: None,
+                level: None,
+                quality: None,
+            },
+        ],
+    };
+    let imported = Inventory::from_events(&stored, None).unwrap();
+    assert_eq!(imported.modules.len(), 1);
+    assert_eq!(imported.modules[0].engineering, "Heavy Duty");
+    assert_eq!(imported.modules[0].class, "A");
+
+    // A generator of a rating the tables don't cover isn't silently dropped
+    let mut stored = stored;
+    stored.items.push(StoredItem {
+        name: "$int_shieldgenerator_size6_class2_name;".to_string(),
+        engineer_modifications: None,
+        level: None,
+        quality: None,
+    });
+    assert!(matches!(
+        Inventory::from_events(&stored, None),
+        Err(Error::UnsupportedRating(_))
+    ));
+}
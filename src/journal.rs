@@ -0,0 +1,440 @@
+/*
+Importing the current ship from the game's journal.
+
+Elite Dangerous writes a `Loadout` event to its journal whenever the ship is
+launched, switched or refitted, listing every module along with its
+engineering.  Rather than retyping all that into flags, the latest event can
+be read from the journal directory, or from a single event saved to a file.
+
+Engineered modules list their final attribute values in
+`Engineering.Modifiers`.  Resistances and booster strength are taken as-is,
+while generator strength is scaled from the unengineered figure for this
+ship's hull mass, since the journal gives it as the multiplier.
+*/
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::blueprint::{ExperimentalModifier, Grade, Modifiers};
+use crate::generator::{engineer_generator, ShieldGeneratorModule};
+use crate::hull::Ship;
+use crate::{
+    engineer_booster, Error, Scenario, ShieldBooster, ShieldBoosterModule, ShieldGenerator,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LoadoutEvent {
+    pub ship: String,
+    pub modules: Vec<LoadoutModule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LoadoutModule {
+    pub slot: String,
+    pub item: String,
+    pub engineering: Option<LoadoutEngineering>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LoadoutEngineering {
+    pub blueprint_name: String,
+    pub level: u8,
+    pub quality: f64,
+    #[serde(rename = "ExperimentalEffect_Localised")]
+    pub experimental_effect: Option<String>,
+    #[serde(default)]
+    pub modifiers: Vec<LoadoutModifier>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LoadoutModifier {
+    pub label: String,
+    /// Absent for text modifiers such as weapon modes
+    #[serde(default)]
+    pub value: f64,
+    #[serde(default)]
+    pub original_value: f64,
+}
+
+#[derive(Deserialize)]
+struct JournalEntry {
+    event: String,
+}
+
+/// Read a single `Loadout` event saved to a file.
+pub fn read_loadout_json(path: &Path) -> Result<LoadoutEvent, Error> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+/// Find the most recent `Loadout` event in a journal directory.
+pub fn read_journal_loadout(dir: &Path) -> Result<LoadoutEvent, Error> {
+    match latest_event(dir, "Loadout")? {
+        Some(line) => Ok(serde_json::from_str(&line)?),
+        None => Err(Error::NoLoadout(dir.display().to_string())),
+    }
+}
+
+/// Find the most recent event of the given kind in a journal directory, as
+/// its line of JSON.
+pub(crate) fn latest_event(dir: &Path, event: &str) -> Result<Option<String>, Error> {
+    let mut journals = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_journal = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.starts_with("Journal.") && name.ends_with(".log"))
+            .unwrap_or(false);
+        if is_journal {
+            journals.push(path);
+        }
+    }
+
+    // Journal names embed their creation time, so sort newest first
+    journals.sort();
+    for path in journals.iter().rev() {
+        if let Some(line) = last_event(BufReader::new(File::open(path)?), event)? {
+            return Ok(Some(line));
+        }
+    }
+
+    Ok(None)
+}
+
+fn last_event<R: BufRead>(reader: R, event: &str) -> Result<Option<String>, Error> {
+    let mut last = None;
+    for line in reader.lines() {
+        let line = line?;
+        let matches = serde_json::from_str::<JournalEntry>(&line)
+            .map(|entry| entry.event == event)
+            .unwrap_or(false);
+        if matches {
+            last = Some(line);
+        }
+    }
+
+    Ok(last)
+}
+
+/// The shield generator and boosters fitted to a ship.
+#[derive(Debug, Clone, Serialize)]
+pub struct Loadout {
+    pub ship: String,
+    pub generator: ShieldGenerator,
+    pub boosters: Vec<ShieldBooster>,
+}
+
+impl Loadout {
+    /// Work out the stats of the shield modules in a `Loadout` event, for the
+    /// given hull mass or the ship's stock mass.
+    pub fn resolve(
+        event: &LoadoutEvent,
+        hull_mass: Option<f64>,
+        ships: &[Ship],
+        generator_modules: &[ShieldGeneratorModule],
+        booster_modules: &[ShieldBoosterModule],
+        experimentals: &[ExperimentalModifier],
+    ) -> Result<Self, Error> {
+        let ship = ships
+            .iter()
+            .find(|ship| ship.symbol.eq_ignore_ascii_case(&event.ship))
+            .ok_or_else(|| Error::UnknownShip(event.ship.clone()))?;
+        let hull_mass = hull_mass.unwrap_or(ship.hull_mass);
+
+        let mut generator = None;
+        let mut boosters = vec![];
+
+        for module in event.modules.iter() {
+            let item = module.item.to_ascii_lowercase();
+            let unsupported = || Error::UnsupportedModule(module.item.clone());
+
+            if item.starts_with("int_shieldgenerator_") {
+                let (class, kind) = generator_item(&item)?.ok_or_else(unsupported)?;
+                let base = generator_modules
+                    .iter()
+                    .find(|m| m.kind == kind && m.class == class)
+                    .ok_or_else(unsupported)?;
+
+                let mut shield = engineer_generator(
+                    ship,
+                    base,
+                    hull_mass,
+                    "None",
+                    "No Experimental Effect",
+                    Grade::MAX,
+                    &Modifiers::default(),
+                );
+
+                if let Some(ref engineering) = module.engineering {
+                    shield.engineering = blueprint_name(&engineering.blueprint_name);
+                    shield.experimental = experimental_name(engineering, experimentals);
+                    shield.grade = Grade::new(engineering.level, engineering.quality)?;
+
+                    for m in engineering.modifiers.iter() {
+                        match m.label.as_str() {
+                            "ShieldGenStrength" if m.original_value > 0.0 => {
+                                shield.shield_strength *= m.value / m.original_value
+                            }
+                            "RegenRate" => shield.regen_rate = m.value,
+                            "ExplosiveResistance" => shield.exp_res = resonance(m.value),
+                            "KineticResistance" => shield.kin_res = resonance(m.value),
+                            "ThermicResistance" => shield.therm_res = resonance(m.value),
+                            _ => (),
+                        }
+                    }
+                }
+
+                generator = Some(shield);
+            } else if item.starts_with("hpt_shieldbooster_") {
+                let rating = booster_item(&item).ok_or_else(unsupported)?;
+                let base = booster_modules
+                    .iter()
+                    .find(|m| m.rating == rating)
+                    .ok_or_else(unsupported)?;
+
+                let mut booster = engineer_booster(
+                    base,
+                    "None",
+                    "No Experimental Effect",
+                    Grade::MAX,
+                    &Modifiers::default(),
+                );
+
+                if let Some(ref engineering) = module.engineering {
+                    booster.engineering = blueprint_name(&engineering.blueprint_name);
+                    booster.experimental = experimental_name(engineering, experimentals);
+                    booster.grade = Grade::new(engineering.level, engineering.quality)?;
+
+                    for m in engineering.modifiers.iter() {
+                        match m.label.as_str() {
+                            "ShieldBonus" => booster.shield_strength_bonus = m.value / 100.0,
+                            "ExplosiveResistance" => booster.exp_res_bonus = resonance(m.value),
+                            "KineticResistance" => booster.kin_res_bonus = resonance(m.value),
+                            "ThermicResistance" => booster.therm_res_bonus = resonance(m.value),
+                            _ => (),
+                        }
+                    }
+                }
+
+                boosters.push(booster);
+            }
+        }
+
+        Ok(Self {
+            ship: ship.ship.clone(),
+            generator: generator.ok_or_else(|| Error::NoShieldGenerator(ship.ship.clone()))?,
+            boosters,
+        })
+    }
+}
+
+/// The class and type of a journal shield generator item, such as
+/// `int_shieldgenerator_size6_class5_strong`, or None if it isn't one.  Only
+/// A-rated generators and C-rated Bi-Weaves are modelled, so other ratings
+/// are an error.
+pub(crate) fn generator_item(item: &str) -> Result<Option<(u8, &'static str)>, Error> {
+    let size = match item
+        .to_ascii_lowercase()
+        .strip_prefix("int_shieldgenerator_size")
+    {
+        Some(size) => size.to_string(),
+        None => return Ok(None),
+    };
+    let (class, kind) = match size.split('_').collect::<Vec<_>>()[..] {
+        [class, "class5"] => (class, "Normal"),
+        [class, "class3", "fast"] => (class, "Bi-Weave"),
+        [class, "class5", "strong"] => (class, "Prismatic"),
+        [_, rating, ..] if rating.starts_with("class") => {
+            return Err(Error::UnsupportedRating(item.to_string()))
+        }
+        _ => return Ok(None),
+    };
+    Ok(class.parse().ok().map(|class| (class, kind)))
+}
+
+/// The rating of a journal shield booster item, such as `hpt_shieldbooster_size0_class5`.
+pub(crate) fn booster_item(item: &str) -> Option<char> {
+    match item
+        .to_ascii_lowercase()
+        .strip_prefix("hpt_shieldbooster_size0_class")?
+    {
+        "1" => Some('E'),
+        "2" => Some('D'),
+        "3" => Some('C'),
+        "4" => Some('B'),
+        "5" => Some('A'),
+        _ => None,
+    }
+}
+
+/// Journal resistances are percentages.
+fn resonance(resistance: f64) -> f64 {
+    1.0 - resistance / 100.0
+}
+
+/// The name the booster and shield tables use for a journal blueprint, or the
+/// game's own name for Enhanced Low Power, which they don't cover.
+pub(crate) fn blueprint_name(symbol: &str) -> String {
+    match symbol.to_ascii_lowercase().as_str() {
+        "shieldbooster_explosive" => "Blast Resistance",
+        "shieldbooster_heavyduty" => "Heavy Duty",
+        "shieldbooster_kinetic" => "Kinetic Resistance",
+        "shieldbooster_resistive" => "Resistance Augmented",
+        "shieldbooster_thermic" => "Thermal Resistance",
+        "shieldgenerator_kinetic" => "Kinetic resistant",
+        "shieldgenerator_optimised" => "Enhanced Low Power",
+        "shieldgenerator_reinforced" => "Reinforced",
+        "shieldgenerator_thermic" => "Thermal resistant",
+        _ => symbol,
+    }
+    .to_string()
+}
+
+/// The name the tables use for a journal experimental effect, falling back on
+/// the game's own name for effects they don't know.
+fn experimental_name(
+    engineering: &LoadoutEngineering,
+    experimentals: &[ExperimentalModifier],
+) -> String {
+    match engineering.experimental_effect {
+        Some(ref name) => experimentals
+            .iter()
+            .find(|m| m.experimental.eq_ignore_ascii_case(name))
+            .map(|m| m.experimental.clone())
+            .unwrap_or_else(|| name.clone()),
+        None => "No Experimental Effect".to_string(),
+    }
+}
+
+impl Scenario {
+    /// This scenario for the loadout's ship, shield class and booster count,
+    /// with boosters of its fitted ratings.
+    pub fn with_loadout(&self, loadout: &Loadout) -> Scenario {
+        let mut test = self.clone();
+        test.ship = loadout.ship.clone();
+        test.shield_class = Some(loadout.generator.class);
+        test.shield_booster_count = loadout.boosters.len();
+        test.booster_ratings.clear();
+        if let Some(rating) = loadout.boosters.iter().map(|b| b.rating).min() {
+            test.shield_booster_rating = rating;
+            if loadout.boosters.iter().any(|b| b.rating != rating) {
+                test.booster_ratings = loadout.boosters.iter().map(|b| b.rating).collect();
+            }
+        }
+        test
+    }
+}
+
+#[test]
+fn test_journal_loadout() {
+    let journal = concat!(
+        r#"{ "timestamp":"2020-01-01T00:00:00Z", "event":"Fileheader", "part":1 }"#,
+        "\n",
+        r#"{ "timestamp":"2020-01-01T00:01:00Z", "event":"Loadout", "Ship":"python", "Modules":[] }"#,
+        "\n",
+        r#"{ "timestamp":"2020-01-01T00:02:00Z", "event":"Loadout", "Ship":"federation_corvette", "Modules":["#,
+        r#"{ "Slot":"Slot01_Size7", "Item":"int_shieldgenerator_size7_class5_strong", "#,
+        r#""Engineering":{ "BlueprintName":"ShieldGenerator_Reinforced", "Level":5, "Quality":1.0, "#,
+        r#""ExperimentalEffect_Localised":"Hi-Cap", "Modifiers":["#,
+        r#"{ "Label":"ShieldGenStrength", "Value":200.0, "OriginalValue":100.0 }, "#,
+        r#"{ "Label":"KineticResistance", "Value":50.0, "OriginalValue":40.0 } ] } }, "#,
+        r#"{ "Slot":"TinyHardpoint1", "Item":"hpt_shieldbooster_size0_class5", "#,
+        r#""Engineering":{ "BlueprintName":"ShieldBooster_HeavyDuty", "Level":3, "Quality":0.5, "#,
+        r#""Modifiers":[ { "Label":"ShieldBonus", "Value":42.8, "OriginalValue":20.0 } ] } }, "#,
+        r#"{ "Slot":"TinyHardpoint2", "Item":"hpt_shieldbooster_size0_class3" } ] }"#,
+        "\n",
+        r#"{ "timestamp":"2020-01-01T00:03:00Z", "event":"Music", "MusicTrack":"NoTrack" }"#,
+    );
+
+    let line = last_event(journal.as_bytes(), "Loadout").unwrap().unwrap();
+    let event: LoadoutEvent = serde_json::from_str(&line).unwrap();
+    assert_eq!(event.ship, "federation_corvette");
+
+    let db = crate::Database::builtin().unwrap();
+    let loadout = db.resolve_loadout(&event, None).unwrap();
+    assert_eq!(loadout.ship, "Federal Corvette");
+
+    let shield = &loadout.generator;
+    assert_eq!(shield.engineering, "Reinforced");
+    assert_eq!(shield.experimental, "Hi-Cap");
+    assert!((shield.kin_res - 0.5).abs() < 1e-9);
+
+    // Doubling the unengineered strength
+    let ship = db
+        .ships
+        .iter()
+        .find(|s| s.ship == "Federal Corvette")
+        .unwrap();
+    let module = db
+        .generator_modules
+        .iter()
+        .find(|m| m.kind == "Prismatic" && m.class == 7)
+        .unwrap();
+    let base =
+        ship.base_shield * crate::generator::calculate_shield_multiplier(module, ship.hull_mass);
+    assert!((shield.shield_strength - 2.0 * base).abs() < 1e-9);
+
+    assert_eq!(loadout.boosters.len(), 2);
+    assert_eq!(loadout.boosters[0].grade, Grade::new(3, 0.5).unwrap());
+    assert!((loadout.boosters[0].shield_strength_bonus - 0.428).abs() < 1e-9);
+    assert_eq!(loadout.boosters[1].rating, 'C');
+    assert_eq!(loadout.boosters[1].engineering, "None");
+
+    let test = Scenario::default().with_loadout(&loadout);
+    assert_eq!(test.ship, "Federal Corvette");
+    assert_eq!(test.shield_class, Some(7));
+    assert_eq!(test.shield_booster_count, 2);
+    assert_eq!(test.shield_booster_rating, 'A');
+    assert_eq!(test.booster_rating_limits(), vec![('A', 1), ('C', 1)]);
+
+    // Blueprints take the names in the blueprint table
+    let event = LoadoutEvent {
+        modules: vec![serde_json::from_str(concat!(
+            r#"{ "Slot":"Slot01_Size6", "Item":"int_shieldgenerator_size6_class3_fast", "#,
+            r#""Engineering":{ "BlueprintName":"ShieldGenerator_Thermic", "Level":4, "Quality":0.5 } }"#,
+        ))
+        .unwrap()],
+        ..event
+    };
+    let shield = db.resolve_loadout(&event, None).unwrap().generator;
+    assert_eq!(shield.kind, "Bi-Weave");
+    assert_eq!(shield.engineering, "Thermal resistant");
+    for symbol in [
+        "ShieldBooster_Explosive",
+        "ShieldBooster_HeavyDuty",
+        "ShieldBooster_Kinetic",
+        "ShieldBooster_Resistive",
+        "ShieldBooster_Thermic",
+        "ShieldGenerator_Kinetic",
+        "ShieldGenerator_Reinforced",
+        "ShieldGenerator_Thermic",
+    ] {
+        let name = blueprint_name(symbol);
+        assert!(db.blueprint_modifiers.iter().any(|m| m.blueprint == name));
+    }
+    assert_eq!(
+        blueprint_name("ShieldGenerator_Optimised"),
+        "Enhanced Low Power"
+    );
+
+    // Only A-rated generators are modelled
+    let event = LoadoutEvent {
+        modules: vec![LoadoutModule {
+            slot: "Slot01_Size6".to_string(),
+            item: "int_shieldgenerator_size6_class3".to_string(),
+            engineering: None,
+        }],
+        ..event
+    };
+    assert!(matches!(
+        db.resolve_loadout(&event, None),
+        Err(Error::UnsupportedRating(_))
+    ));
+}
@@ -35,14 +35,14 @@ impl KDTreeNode {
 
         let potency = point_list.last().expect("last index")[axis];
         let location = point_list[median].clone();
-        let (mut left, right) = point_list.split_at_mut(median);
-        let (_, mut right) = right.split_at_mut(1);
+        let (left, right) = point_list.split_at_mut(median);
+        let (_, right) = right.split_at_mut(1);
 
         let node = KDTreeNode {
             potency,
             location,
-            left: KDTreeNode::import_depth(&mut left, depth + 1),
-            right: KDTreeNode::import_depth(&mut right, depth + 1),
+            left: KDTreeNode::import_depth(left, depth + 1),
+            right: KDTreeNode::import_depth(right, depth + 1),
         };
 
         Some(Box::new(node))
@@ -63,13 +63,12 @@ impl KDTreeNode {
 
         // XXX: This should be an identity check.
         if self.location != point {
-            // this node might beat the point
-            if !self
-                .location
-                .iter()
-                .take(k)
-                .zip(point.iter())
-                .any(|(loc, p)| loc <= p)
+            // this node might beat the point: it must be at least as good on
+            // every dimension, and strictly better on at least one.
+            let location = &self.location[..k];
+            let point = &point[..k];
+            if location.iter().zip(point).all(|(loc, p)| loc >= p)
+                && location.iter().zip(point).any(|(loc, p)| loc > p)
             {
                 return true;
             }
@@ -94,17 +93,45 @@ impl KDTreeNode {
     }
 }
 
+/// Find the points which no other point dominates, returning their indexes.
+///
+/// Higher values are better on every axis.
+pub fn non_dominated(points: &[Vec<f64>]) -> Vec<usize> {
+    let tagged: Vec<Vec<f64>> = points
+        .iter()
+        .enumerate()
+        .map(|(id, point)| {
+            let mut point = point.clone();
+            point.push(id as f64);
+            point
+        })
+        .collect();
+
+    let mut tmp = tagged.clone();
+    let tree = match KDTreeNode::from_points(&mut tmp[..]) {
+        Some(tree) => tree,
+        None => return vec![],
+    };
+
+    tagged
+        .iter()
+        .enumerate()
+        .filter(|(_, point)| !tree.dominates(&point[..]))
+        .map(|(id, _)| id)
+        .collect()
+}
+
 #[test]
 fn test_kdtree() {
     let item_list = [
-        // (foo, bar, baz)
-        vec![10.0, 20.0, 10.0], // should get eliminated
-        vec![10.0, 20.0, 30.0], // should stay because it's the only 30 baz that has 20 bar
-        vec![30.0, 20.0, 10.0], // should stay because it's the only 30 foo that has 20 bar
-        vec![30.0, 10.0, 30.0], // should stay because it's the only 30 foo that has 30 baz
-        vec![10.0, 30.0, 20.0], // should stay because it's the only 30 bar that has 20 baz
-        vec![30.0, 10.0, 20.0], // should get eliminated
-        vec![20.0, 30.0, 10.0], // should stay because it's the only 30 bar that has 20 foo
+        // (foo, bar, baz, id)
+        vec![10.0, 20.0, 10.0, 0.0], // should get eliminated
+        vec![10.0, 20.0, 30.0, 1.0], // should stay because it's the only 30 baz that has 20 bar
+        vec![30.0, 20.0, 10.0, 2.0], // should stay because it's the only 30 foo that has 20 bar
+        vec![30.0, 10.0, 30.0, 3.0], // should stay because it's the only 30 foo that has 30 baz
+        vec![10.0, 30.0, 20.0, 4.0], // should stay because it's the only 30 bar that has 20 baz
+        vec![30.0, 10.0, 20.0, 5.0], // should get eliminated
+        vec![20.0, 30.0, 10.0, 6.0], // should stay because it's the only 30 bar that has 20 foo
     ];
 
     let eliminations = [0, 5];
@@ -116,3 +143,18 @@ fn test_kdtree() {
         assert!(eliminations.contains(&i) == tree.dominates(&item[..]));
     }
 }
+
+#[test]
+fn test_non_dominated() {
+    let item_list = [
+        vec![1.0, 1.0],
+        vec![2.0, 2.0],
+        vec![3.0, 1.0],
+        vec![2.0, 2.0],
+        vec![0.0, 3.0],
+    ];
+
+    // Identical points never eliminate each other
+    assert_eq!(non_dominated(&item_list), vec![1, 2, 3, 4]);
+    assert!(non_dominated(&[]).is_empty());
+}
@@ -0,0 +1,1705 @@
+//! Elite Dangerous Shield Optimiser
+//!
+//! Finds the combination of shield generator and shield booster engineering
+//! which survives longest against a given attacker.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use itertools::Itertools;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use structopt::StructOpt;
+
+pub mod allocation;
+pub mod blueprint;
+pub mod combinations;
+pub mod constraint;
+pub mod evaluate;
+pub mod filter;
+pub mod fitting;
+pub mod generator;
+pub mod hull;
+pub mod inventory;
+pub mod journal;
+pub mod kdtree;
+pub mod objective;
+pub mod pareto;
+pub mod scb;
+pub mod simulation;
+pub mod sweep;
+pub mod thargoid;
+pub mod upgrade;
+pub mod weapon;
+
+use crate::blueprint::{BlueprintModifier, ExperimentalModifier, Grade, Modifiers, ModuleKind};
+use crate::constraint::{Constraint, Tally};
+use crate::evaluate::ShieldSpec;
+use crate::filter::{BoosterCount, ModuleFilter};
+use crate::fitting::GuardianReinforcement;
+use crate::generator::ShieldGeneratorModule;
+use crate::hull::{Bulkhead, HullReinforcement, HullReinforcementSpec, HullStat, Ship};
+use crate::inventory::Inventory;
+use crate::journal::{Loadout, LoadoutEvent};
+use crate::objective::{Objective, Weights};
+use crate::scb::{CellBank, CellBankSpec, ShieldCellBank, ShieldCellBankVariant};
+use crate::thargoid::ThargoidProfile;
+use crate::weapon::{Weapon, WeaponModification, WeaponModule, WeaponSpec};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all(deserialize = "PascalCase"))]
+pub struct ShieldGenerator {
+    pub ship: String,
+    pub class: u8,
+    #[serde(rename(deserialize = "Type", serialize = "type"))]
+    pub kind: String,
+    pub engineering: String,
+    pub experimental: String,
+    #[serde(default, skip_deserializing)]
+    pub grade: Grade,
+    pub shield_strength: f64,
+    pub regen_rate: f64,
+    pub exp_res: f64,
+    pub kin_res: f64,
+    pub therm_res: f64,
+    #[serde(default = "full_resonance")]
+    pub caus_res: f64,
+    /// MW drawn while powered; taken from the generator module table if not given
+    #[serde(default)]
+    pub power_draw: f64,
+}
+
+fn full_resonance() -> f64 {
+    1.0
+}
+
+/// A shield booster variant.
+///
+/// The CSV holds resistance bonuses; once loaded into a `Database` these are
+/// converted to resonance multipliers (1 - resistance).
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all(deserialize = "PascalCase"))]
+pub struct ShieldBooster {
+    pub rating: char,
+    pub engineering: String,
+    pub experimental: String,
+    #[serde(default, skip_deserializing)]
+    pub grade: Grade,
+    pub shield_strength_bonus: f64,
+    pub exp_res_bonus: f64,
+    pub kin_res_bonus: f64,
+    pub therm_res_bonus: f64,
+    /// MW drawn while powered; taken from the booster module table if not given
+    #[serde(default)]
+    pub power_draw: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoadoutStat {
+    pub hit_points: f64,
+    pub regen_rate: f64,
+    pub exp_res: f64,
+    pub kin_res: f64,
+    pub therm_res: f64,
+    pub caus_res: f64,
+    /// MW drawn by the generator, boosters and any Shield Cell Banks
+    pub power_draw: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BoosterStat {
+    pub exp_modifier: f64,
+    pub kin_modifier: f64,
+    pub therm_modifier: f64,
+    pub hit_point_bonus: f64,
+    pub power_draw: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestResult {
+    pub actual_dps: f64,
+    pub survival_time: f64,
+    /// Shield survival time plus hull survival time, when a hull is given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_to_kill: Option<f64>,
+    pub shield: ShieldGenerator,
+    pub boosters: Vec<ShieldBooster>,
+    pub stats: LoadoutStat,
+}
+
+/// The ship, fitting limits and attacker to optimise against.
+#[derive(Debug, Clone, StructOpt, Serialize)]
+pub struct Scenario {
+    /// Number of shield boosters to fit
+    #[structopt(short, long, default_value = "1")]
+    pub shield_booster_count: usize,
+    /// Shield booster rating, A-E
+    #[structopt(long, default_value = "A")]
+    pub shield_booster_rating: char,
+    /// Rating of each shield booster, e.g. "A,A,A,C,C", to mix ratings
+    /// (overrides --shield-booster-count and --shield-booster-rating)
+    #[structopt(long, use_delimiter = true)]
+    pub booster_ratings: Vec<char>,
+    /// Explosive damage per second
+    #[structopt(short, long, default_value = "0")]
+    pub explosive_dps: f64,
+    /// Kinetic damage per second
+    #[structopt(short, long, default_value = "0")]
+    pub kinetic_dps: f64,
+    /// Thermal damage per second
+    #[structopt(short, long, default_value = "0")]
+    pub thermal_dps: f64,
+    /// Absolute damage per second
+    #[structopt(short, long, default_value = "0")]
+    pub absolute_dps: f64,
+    /// Caustic damage per second
+    #[structopt(long, default_value = "0")]
+    pub caustic_dps: f64,
+    /// Attacker shot success ratio, 0-1
+    #[structopt(short, long, default_value = "0.5")]
+    pub damage_effectiveness: f64,
+    /// Explosive shot success ratio (default: --damage-effectiveness)
+    #[structopt(long)]
+    pub explosive_effectiveness: Option<f64>,
+    /// Kinetic shot success ratio (default: --damage-effectiveness)
+    #[structopt(long)]
+    pub kinetic_effectiveness: Option<f64>,
+    /// Thermal shot success ratio (default: --damage-effectiveness)
+    #[structopt(long)]
+    pub thermal_effectiveness: Option<f64>,
+    /// Absolute shot success ratio (default: --damage-effectiveness)
+    #[structopt(long)]
+    pub absolute_effectiveness: Option<f64>,
+    /// Caustic shot success ratio (default: --damage-effectiveness)
+    #[structopt(long)]
+    pub caustic_effectiveness: Option<f64>,
+    /// Mj available via Shield Cell Banks
+    #[structopt(long, default_value = "0")]
+    pub shield_cell_mj: f64,
+    /// Mj provided by Guardian Shield Reinforcements
+    #[structopt(long, default_value = "0")]
+    pub reinforced_mj: f64,
+    /// Filter out prismatic shields
+    #[structopt(long)]
+    pub disable_prismatic: bool,
+    /// Only consider this shield generator type, e.g. "Bi-Weave" (repeatable)
+    #[structopt(long = "only-shield-type", number_of_values = 1)]
+    pub only_shield_types: Vec<String>,
+    /// Only consider this shield generator, e.g. "Bi-Weave,Reinforced,Fast Charge"
+    #[structopt(long)]
+    pub require_shield: Option<ShieldSpec>,
+    /// Fit this booster, e.g. "3x Heavy Duty,Super Capacitors", and search
+    /// only the remaining slots (repeatable)
+    #[structopt(long = "require-booster", number_of_values = 1)]
+    pub require_boosters: Vec<BoosterCount>,
+    /// Most copies of any one booster to fit, 0 for no limit
+    #[structopt(long, default_value = "0")]
+    pub max_duplicates: usize,
+    /// Most copies of a booster to fit, e.g. "2x Resistance Augmented,Force Block",
+    /// such as the number owned (repeatable)
+    #[structopt(long = "booster-cap", number_of_values = 1)]
+    pub booster_caps: Vec<BoosterCount>,
+    /// Modules owned, if the search may use only those
+    #[structopt(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inventory: Option<Inventory>,
+    /// Disregard modules with this blueprint, e.g. "Heavy Duty" (repeatable)
+    #[structopt(long = "exclude-blueprint", number_of_values = 1)]
+    pub exclude_blueprints: Vec<String>,
+    /// Disregard modules with this experimental effect, e.g. "Super Capacitors" (repeatable)
+    #[structopt(long = "exclude-experimental", number_of_values = 1)]
+    pub exclude_experimentals: Vec<String>,
+    /// Disregard shields that take longer than this many seconds to regenerate from 50%
+    #[structopt(long, alias = "max-regen-time")]
+    pub regen_time_limit: Option<f64>,
+    /// Minimum shield hit points, in Mj
+    #[structopt(long)]
+    pub min_hp: Option<f64>,
+    /// Minimum explosive resistance, 0-1
+    #[structopt(long)]
+    pub min_exp_res: Option<f64>,
+    /// Minimum kinetic resistance, 0-1
+    #[structopt(long)]
+    pub min_kin_res: Option<f64>,
+    /// Minimum thermal resistance, 0-1
+    #[structopt(long)]
+    pub min_therm_res: Option<f64>,
+    /// Minimum hit points against explosive damage alone, in Mj
+    #[structopt(long)]
+    pub min_explosive_ehp: Option<f64>,
+    /// Minimum hit points against kinetic damage alone, in Mj
+    #[structopt(long)]
+    pub min_kinetic_ehp: Option<f64>,
+    /// Minimum hit points against thermal damage alone, in Mj
+    #[structopt(long)]
+    pub min_thermal_ehp: Option<f64>,
+    /// Constraint on the result, e.g. "kin_res >= 0.5" or "regen_time <= 60" (repeatable)
+    #[structopt(long = "constraint", number_of_values = 1)]
+    pub constraints: Vec<Constraint>,
+    /// Power plant output in MW, to disregard loadouts it can't run
+    #[structopt(long)]
+    pub power_available: Option<f64>,
+    /// MW drawn by the rest of the ship; with power priorities, only count
+    /// modules which stay powered alongside the shields
+    #[structopt(long, default_value = "0")]
+    pub power_used: f64,
+    /// What to optimise for: survival, hit-points, effective-hit-points,
+    /// regen-time, remaining or weighted
+    #[structopt(long, default_value = "survival")]
+    pub objective: Objective,
+    /// Seconds into the fight to measure the remaining shield, for --objective remaining
+    #[structopt(long, default_value = "60")]
+    pub objective_time: f64,
+    /// Weights for --objective weighted, e.g. "survival=1,ehp=0.01,regen=2"
+    #[structopt(long, default_value = "survival=1")]
+    pub objective_weights: Weights,
+    /// Require experimental effects
+    #[structopt(long)]
+    pub force_experimental: bool,
+    /// Disable pre-filtering (debugging)
+    #[structopt(long)]
+    pub disable_filter: bool,
+    /// Ship name
+    #[structopt(long, default_value = "Anaconda")]
+    pub ship: String,
+    /// Hull mass in tonnes, to derive shield strengths for a non-stock build
+    #[structopt(long)]
+    pub hull_mass: Option<f64>,
+    /// Shield class (default: maximum possible)
+    #[structopt(long)]
+    pub shield_class: Option<u8>,
+    /// Engineering grade of the shield boosters, 1-5
+    #[structopt(long, default_value = "5")]
+    pub booster_grade: u8,
+    /// How well the booster grade rolled, 0-1
+    #[structopt(long, default_value = "1")]
+    pub booster_roll: f64,
+    /// Engineering grade of the shield generator, 1-5
+    #[structopt(long, default_value = "5")]
+    pub shield_grade: u8,
+    /// How well the shield generator grade rolled, 0-1
+    #[structopt(long, default_value = "1")]
+    pub shield_roll: f64,
+    /// Number of loadouts to report
+    #[structopt(long, default_value = "1")]
+    pub top: usize,
+    /// Fitted Shield Cell Bank, e.g. "8B,Specialised,Boss Cells" (repeatable)
+    #[structopt(long = "scb", number_of_values = 1)]
+    pub cell_banks: Vec<CellBankSpec>,
+    /// Bulkheads fitted, e.g. "Military", to report time to kill including the hull
+    #[structopt(long)]
+    pub bulkheads: Option<String>,
+    /// Fitted Hull Reinforcement Package, e.g. "5D" (repeatable)
+    #[structopt(long = "hrp", number_of_values = 1)]
+    pub hull_reinforcements: Vec<HullReinforcementSpec>,
+    /// Attacker armour piercing; weapons below the hull's hardness do reduced damage
+    #[structopt(long)]
+    pub armour_piercing: Option<f64>,
+    /// Attacker weapons, e.g. "2x C3 Gimballed Multi-cannon,Overcharged,Incendiary Rounds",
+    /// added to the per-type DPS (repeatable)
+    #[structopt(long = "weapon", number_of_values = 1)]
+    pub weapons: Vec<WeaponSpec>,
+    /// Use weapons' sustained DPS, including reloads, rather than burst DPS
+    #[structopt(long)]
+    pub sustained_dps: bool,
+    /// Thargoid attack profile added to the attacker, e.g. "Hydra"
+    #[structopt(long)]
+    pub thargoid: Option<String>,
+    /// Seconds between shutdown field pulses (default: from --thargoid)
+    #[structopt(long)]
+    pub shutdown_interval: Option<f64>,
+    /// Seconds each shutdown field stops shield regen (default: from --thargoid, else 15)
+    #[structopt(long)]
+    pub shutdown_duration: Option<f64>,
+    /// A Guardian Shutdown Neutraliser is fitted, so shutdown fields have no effect
+    #[structopt(long)]
+    pub shutdown_neutraliser: bool,
+}
+
+impl Scenario {
+    /// Number of shield boosters to fit.
+    pub fn booster_count(&self) -> usize {
+        if self.booster_ratings.is_empty() {
+            self.shield_booster_count
+        } else {
+            self.booster_ratings.len()
+        }
+    }
+
+    /// Each booster rating to fit, best first, and how many of it.
+    pub fn booster_rating_limits(&self) -> Vec<(char, usize)> {
+        if self.booster_ratings.is_empty() {
+            return vec![(
+                self.shield_booster_rating.to_ascii_uppercase(),
+                self.shield_booster_count,
+            )];
+        }
+
+        let mut ratings: Vec<char> = self
+            .booster_ratings
+            .iter()
+            .map(|r| r.to_ascii_uppercase())
+            .collect();
+        ratings.sort_unstable();
+        ratings
+            .iter()
+            .group_by(|&&r| r)
+            .into_iter()
+            .map(|(rating, group)| (rating, group.count()))
+            .collect()
+    }
+
+    /// Whether boosters can be filtered out ahead of the search, which relies
+    /// on more hit points and resistance never making a loadout worse, and on
+    /// boosters being available in any number.  Like the regen time limit, an
+    /// upper limit on regen time doesn't count.
+    pub fn prefilter(&self) -> bool {
+        !self.disable_filter
+            && !objective::penalises_hit_points(self)
+            && self.max_duplicates == 0
+            && self.booster_caps.is_empty()
+            && self.inventory.is_none()
+            && constraint::constraints(self)
+                .iter()
+                .all(|c| c.at_least || c.metric == constraint::Metric::RegenTime)
+    }
+
+    /// MW left for the shields, if the power plant's output is given.
+    pub fn power_budget(&self) -> Option<f64> {
+        self.power_available
+            .map(|available| available - self.power_used)
+    }
+
+    /// Shot success ratio for explosive, kinetic, thermal, absolute and caustic damage.
+    pub fn effectiveness(&self) -> [f64; 5] {
+        let eff = |e: Option<f64>| e.unwrap_or(self.damage_effectiveness);
+        [
+            eff(self.explosive_effectiveness),
+            eff(self.kinetic_effectiveness),
+            eff(self.thermal_effectiveness),
+            eff(self.absolute_effectiveness),
+            eff(self.caustic_effectiveness),
+        ]
+    }
+
+    /// Share of the fight the shield spends under fire, and so not regenerating.
+    ///
+    /// Hits from different weapons overlap, so this is the best hit rate of
+    /// any damage type the attacker actually deals.
+    pub fn time_under_fire(&self) -> f64 {
+        let dps = [
+            self.explosive_dps,
+            self.kinetic_dps,
+            self.thermal_dps,
+            self.absolute_dps,
+            self.caustic_dps,
+        ];
+        self.effectiveness()
+            .iter()
+            .zip(dps.iter())
+            .filter(|(_, &dps)| dps > 0.0)
+            .map(|(&eff, _)| eff)
+            .fold(None, |max: Option<f64>, eff| {
+                Some(max.map_or(eff, |m| m.max(eff)))
+            })
+            .unwrap_or(self.damage_effectiveness)
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self {
+            shield_booster_count: 1,
+            shield_booster_rating: 'A',
+            booster_ratings: vec![],
+            explosive_dps: 0.0,
+            kinetic_dps: 0.0,
+            thermal_dps: 0.0,
+            absolute_dps: 0.0,
+            caustic_dps: 0.0,
+            damage_effectiveness: 0.5,
+            explosive_effectiveness: None,
+            kinetic_effectiveness: None,
+            thermal_effectiveness: None,
+            absolute_effectiveness: None,
+            caustic_effectiveness: None,
+            shield_cell_mj: 0.0,
+            reinforced_mj: 0.0,
+            disable_prismatic: false,
+            only_shield_types: vec![],
+            require_shield: None,
+            require_boosters: vec![],
+            max_duplicates: 0,
+            booster_caps: vec![],
+            inventory: None,
+            exclude_blueprints: vec![],
+            exclude_experimentals: vec![],
+            regen_time_limit: None,
+            min_hp: None,
+            min_exp_res: None,
+            min_kin_res: None,
+            min_therm_res: None,
+            min_explosive_ehp: None,
+            min_kinetic_ehp: None,
+            min_thermal_ehp: None,
+            constraints: vec![],
+            power_available: None,
+            power_used: 0.0,
+            objective: Objective::Survival,
+            objective_time: 60.0,
+            objective_weights: Weights {
+                survival: 1.0,
+                ..Weights::default()
+            },
+            force_experimental: false,
+            disable_filter: false,
+            ship: "Anaconda".to_string(),
+            hull_mass: None,
+            shield_class: None,
+            booster_grade: 5,
+            booster_roll: 1.0,
+            shield_grade: 5,
+            shield_roll: 1.0,
+            top: 1,
+            cell_banks: vec![],
+            bulkheads: None,
+            hull_reinforcements: vec![],
+            armour_piercing: None,
+            weapons: vec![],
+            sustained_dps: false,
+            thargoid: None,
+            shutdown_interval: None,
+            shutdown_duration: None,
+            shutdown_neutraliser: false,
+        }
+    }
+}
+
+// Kept by hand: rustfmt would spread every struct variant over several lines
+// to match the documented ones
+#[rustfmt::skip]
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Csv(csv::Error),
+    UnknownShip(String),
+    InvalidShieldClass { class: u8, min: u8, max: u8 },
+    InvalidBoosterRating(char),
+    UnknownCellBank(String),
+    UnknownBulkhead(String),
+    UnknownHullReinforcement(String),
+    UnknownWeapon(String),
+    UnknownThargoid(String),
+    HullTooHeavy(f64),
+    InvalidGrade { grade: u8, roll: f64 },
+    UnknownBlueprint(String),
+    Json(serde_json::Error),
+    NoLoadout(String),
+    NoStoredModules(String),
+    NoShieldGenerator(String),
+    UnsupportedModule(String),
+    UnsupportedRating(String),
+    UnknownShield(String),
+    UnknownBooster(String),
+    InsufficientPower { required: f64, available: f64 },
+    InvalidUtility(String),
+    InvalidObjective(String),
+    InvalidConstraint(String),
+    Infeasible(Vec<String>),
+    TooManyUtilities { required: usize, slots: usize },
+    /// Required boosters, of a given rating if any, outnumbering the slots
+    TooManyPinnedBoosters {
+        rating: Option<char>,
+        pinned: usize,
+        slots: usize,
+    },
+    TooManyBoosters(usize),
+    /// No shield generators or boosters pass the module filters
+    NoCandidates(&'static str),
+    InvalidSweep(&'static str),
+    InvalidSimulation(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Csv(e) => write!(f, "{}", e),
+            Error::UnknownShip(ship) => write!(f, "Unknown ship: {}", ship),
+            Error::InvalidShieldClass { class, .. } => {
+                write!(f, "Invalid shield class: {}", class)
+            }
+            Error::InvalidBoosterRating(rating) => {
+                write!(f, "Invalid booster rating: {}", rating)
+            }
+            Error::UnknownCellBank(spec) => write!(f, "Unknown shield cell bank: {}", spec),
+            Error::UnknownBulkhead(kind) => write!(f, "Unknown bulkheads: {}", kind),
+            Error::UnknownHullReinforcement(spec) => {
+                write!(f, "Unknown hull reinforcement package: {}", spec)
+            }
+            Error::UnknownWeapon(spec) => write!(f, "Unknown weapon: {}", spec),
+            Error::UnknownThargoid(name) => write!(f, "Unknown Thargoid: {}", name),
+            Error::HullTooHeavy(mass) => {
+                write!(f, "No shield generator can protect a {:.0}t hull", mass)
+            }
+            Error::InvalidGrade { grade, roll } => {
+                write!(f, "Invalid engineering grade: {} with roll {}", grade, roll)
+            }
+            Error::UnknownBlueprint(name) => write!(f, "Unknown blueprint: {}", name),
+            Error::Json(e) => write!(f, "{}", e),
+            Error::NoLoadout(dir) => write!(f, "No Loadout event found in {}", dir),
+            Error::NoStoredModules(dir) => write!(f, "No StoredModules event found in {}", dir),
+            Error::NoShieldGenerator(ship) => write!(f, "No shield generator fitted to {}", ship),
+            Error::UnsupportedModule(item) => write!(f, "Unsupported module: {}", item),
+            Error::UnsupportedRating(item) => write!(
+                f,
+                "Unsupported shield generator rating: {} (only A-rated generators and C-rated Bi-Weaves are modelled)",
+                item
+            ),
+            Error::UnknownShield(spec) => write!(f, "Unknown shield generator: {}", spec),
+            Error::UnknownBooster(spec) => write!(f, "Unknown shield booster: {}", spec),
+            Error::InsufficientPower {
+                required,
+                available,
+            } => write!(
+                f,
+                "Not enough power: the shields need at least {:.2} MW but only {:.2} MW is available",
+                required, available
+            ),
+            Error::InvalidUtility(spec) => write!(f, "Invalid utility: {}", spec),
+            Error::InvalidObjective(spec) => write!(f, "Invalid objective: {}", spec),
+            Error::InvalidConstraint(spec) => write!(f, "Invalid constraint: {}", spec),
+            Error::Infeasible(unmet) if unmet.is_empty() => {
+                write!(f, "No loadout meets every constraint at once")
+            }
+            Error::Infeasible(unmet) => {
+                write!(f, "No loadout meets {}", unmet.join(", "))
+            }
+            Error::TooManyUtilities { required, slots } => write!(
+                f,
+                "{} required utilities don't fit in {} utility slots",
+                required, slots
+            ),
+            Error::TooManyPinnedBoosters {
+                rating,
+                pinned,
+                slots,
+            } => {
+                let rated = rating.map(|r| format!("{}-rated ", r)).unwrap_or_default();
+                write!(
+                    f,
+                    "{} required {}boosters don't fit in {} {}booster slots",
+                    pinned, rated, slots, rated
+                )
+            }
+            Error::TooManyBoosters(count) => write!(
+                f,
+                "{} shield boosters pass the module filters, but at most {} can be searched",
+                count,
+                combinations::MAX_ITEMS
+            ),
+            Error::NoCandidates(modules) => {
+                write!(f, "Not enough {} pass the module filters", modules)
+            }
+            Error::InvalidSweep(reason) => write!(f, "Can't sweep the damage mix: {}", reason),
+            Error::InvalidSimulation(reason) => write!(f, "Can't simulate the fight: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        Error::Csv(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+/// Shield generators, boosters, cell banks, hull modules, weapons and Thargoid
+/// attack profiles available to the search.
+#[derive(Debug, Clone)]
+pub struct Database {
+    pub generators: Vec<ShieldGenerator>,
+    pub boosters: Vec<ShieldBooster>,
+    pub cell_banks: Vec<ShieldCellBank>,
+    pub cell_bank_variants: Vec<ShieldCellBankVariant>,
+    pub ships: Vec<Ship>,
+    pub bulkheads: Vec<Bulkhead>,
+    pub hull_reinforcements: Vec<HullReinforcement>,
+    pub weapons: Vec<WeaponModule>,
+    pub weapon_modifications: Vec<WeaponModification>,
+    pub thargoids: Vec<ThargoidProfile>,
+    pub generator_modules: Vec<ShieldGeneratorModule>,
+    pub booster_modules: Vec<ShieldBoosterModule>,
+    pub blueprint_modifiers: Vec<BlueprintModifier>,
+    pub experimental_modifiers: Vec<ExperimentalModifier>,
+    pub guardian_reinforcements: Vec<GuardianReinforcement>,
+}
+
+/// A booster of the given rating with a blueprint's modifiers applied.
+pub fn engineer_booster(
+    module: &ShieldBoosterModule,
+    blueprint: &str,
+    experimental: &str,
+    grade: Grade,
+    modifiers: &Modifiers,
+) -> ShieldBooster {
+    ShieldBooster {
+        rating: module.rating,
+        engineering: blueprint.to_string(),
+        experimental: experimental.to_string(),
+        grade,
+        shield_strength_bonus: (1.0 + module.shield_strength_bonus) * modifiers.shield_strength
+            - 1.0,
+        exp_res_bonus: modifiers.exp_res,
+        kin_res_bonus: modifiers.kin_res,
+        therm_res_bonus: modifiers.therm_res,
+        power_draw: module.power_draw,
+    }
+}
+
+/// Unengineered shield booster stats for a rating.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ShieldBoosterModule {
+    pub rating: char,
+    pub shield_strength_bonus: f64,
+    /// MW drawn while powered
+    pub power_draw: f64,
+}
+
+impl Database {
+    /// Load the built-in ship, shield and booster tables.
+    pub fn builtin() -> Result<Self, Error> {
+        Self::from_readers(
+            &include_bytes!("../data/Shields.csv")[..],
+            &include_bytes!("../data/ShieldBoosterVariants.csv")[..],
+        )
+    }
+
+    /// Load the given shield and booster tables alongside the built-in
+    /// Shield Cell Bank, hull, weapon, Thargoid and generator derivation tables.
+    pub fn from_readers<S: Read, B: Read>(shields: S, boosters: B) -> Result<Self, Error> {
+        let mut db = Self {
+            generators: vec![],
+            boosters: vec![],
+            cell_banks: vec![],
+            cell_bank_variants: parse_csv(
+                &include_bytes!("../data/ShieldCellBankVariants.csv")[..],
+            )?,
+            ships: parse_csv(&include_bytes!("../data/Ships.csv")[..])?,
+            bulkheads: hull::load_bulkheads(&include_bytes!("../data/Bulkheads.csv")[..])?,
+            hull_reinforcements: hull::load_hull_reinforcements(
+                &include_bytes!("../data/HullReinforcements.csv")[..],
+            )?,
+            weapons: parse_csv(&include_bytes!("../data/Weapons.csv")[..])?,
+            weapon_modifications: parse_csv(
+                &include_bytes!("../data/WeaponModifications.csv")[..],
+            )?,
+            thargoids: parse_csv(&include_bytes!("../data/ThargoidProfiles.csv")[..])?,
+            generator_modules: parse_csv(
+                &include_bytes!("../data/ShieldGeneratorModules.csv")[..],
+            )?,
+            booster_modules: parse_csv(&include_bytes!("../data/ShieldBoosterModules.csv")[..])?,
+            blueprint_modifiers: parse_csv(&include_bytes!("../data/Blueprints.csv")[..])?,
+            experimental_modifiers: parse_csv(
+                &include_bytes!("../data/ExperimentalEffects.csv")[..],
+            )?,
+            guardian_reinforcements: parse_csv(
+                &include_bytes!("../data/GuardianShieldReinforcements.csv")[..],
+            )?,
+        };
+        db.load_shields(shields)?;
+        db.load_boosters(boosters)?;
+        db.load_cell_banks(&include_bytes!("../data/ShieldCellBanks.csv")[..])?;
+        Ok(db)
+    }
+
+    /// Replace the shield generator list.
+    pub fn load_shields<R: Read>(&mut self, shields: R) -> Result<(), Error> {
+        let modules = &self.generator_modules;
+        self.generators = parse_csv::<ShieldGenerator, _>(shields)?
+            .into_iter()
+            .map(|mut gen| {
+                if gen.power_draw == 0.0 {
+                    gen.power_draw = modules
+                        .iter()
+                        .find(|m| m.kind == gen.kind && m.class == gen.class)
+                        .map_or(0.0, |m| m.power_draw);
+                }
+                gen
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Replace the shield booster list.
+    pub fn load_boosters<R: Read>(&mut self, boosters: R) -> Result<(), Error> {
+        let modules = &self.booster_modules;
+        self.boosters = parse_csv::<ShieldBooster, _>(boosters)?
+            .into_iter()
+            .map(|mut booster| {
+                // Convert resistances to resonances
+                booster.exp_res_bonus = 1.0 - booster.exp_res_bonus;
+                booster.kin_res_bonus = 1.0 - booster.kin_res_bonus;
+                booster.therm_res_bonus = 1.0 - booster.therm_res_bonus;
+                if booster.power_draw == 0.0 {
+                    booster.power_draw = modules
+                        .iter()
+                        .find(|m| m.rating == booster.rating)
+                        .map_or(0.0, |m| m.power_draw);
+                }
+                booster
+            })
+            .collect();
+        Ok(())
+    }
+
+    /// Replace the Shield Cell Bank list.
+    pub fn load_cell_banks<R: Read>(&mut self, cell_banks: R) -> Result<(), Error> {
+        self.cell_banks = parse_csv(cell_banks)?;
+        Ok(())
+    }
+
+    pub fn load_shields_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        self.load_shields(std::fs::File::open(path)?)
+    }
+
+    pub fn load_boosters_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        self.load_boosters(std::fs::File::open(path)?)
+    }
+
+    pub fn load_cell_banks_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        self.load_cell_banks(std::fs::File::open(path)?)
+    }
+
+    /// Work out the effective stats of each fitted Shield Cell Bank.
+    pub fn resolve_cell_banks(&self, specs: &[CellBankSpec]) -> Result<Vec<CellBank>, Error> {
+        specs
+            .iter()
+            .map(|spec| CellBank::resolve(spec, &self.cell_banks, &self.cell_bank_variants))
+            .collect()
+    }
+
+    /// Work out the effective stats of each group of attacker weapons.
+    pub fn resolve_weapons(&self, specs: &[WeaponSpec]) -> Result<Vec<Weapon>, Error> {
+        specs
+            .iter()
+            .map(|spec| Weapon::resolve(spec, &self.weapons, &self.weapon_modifications))
+            .collect()
+    }
+
+    /// Work out the shield modules of a journal `Loadout` event.
+    pub fn resolve_loadout(
+        &self,
+        event: &LoadoutEvent,
+        hull_mass: Option<f64>,
+    ) -> Result<Loadout, Error> {
+        Loadout::resolve(
+            event,
+            hull_mass,
+            &self.ships,
+            &self.generator_modules,
+            &self.booster_modules,
+            &self.experimental_modifiers,
+        )
+    }
+
+    /// Look up a Thargoid attack profile by name.
+    pub fn thargoid(&self, name: &str) -> Result<&ThargoidProfile, Error> {
+        self.thargoids
+            .iter()
+            .find(|profile| profile.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| Error::UnknownThargoid(name.to_string()))
+    }
+
+    /// Work out the scenario's hull, if it has bulkheads or hull reinforcements.
+    pub fn resolve_hull(&self, scenario: &Scenario) -> Result<Option<HullStat>, Error> {
+        HullStat::resolve(
+            scenario,
+            &self.ships,
+            &self.bulkheads,
+            &self.hull_reinforcements,
+        )
+    }
+
+    /// Shield generators for a ship, engineered to the given grade.
+    ///
+    /// These come from the shield table unless a hull mass or a lesser grade
+    /// is given or the ship isn't in it, in which case they're derived from
+    /// the ship's base shield and hull mass.
+    pub fn ship_generators(
+        &self,
+        ship: &str,
+        hull_mass: Option<f64>,
+        grade: Grade,
+    ) -> Result<Vec<ShieldGenerator>, Error> {
+        if hull_mass.is_none() && grade.is_max() {
+            let generators: Vec<ShieldGenerator> = self
+                .generators
+                .iter()
+                .filter(|gen| gen.ship == ship)
+                .cloned()
+                .collect();
+            if !generators.is_empty() {
+                return Ok(generators);
+            }
+        }
+
+        let ship = self
+            .ships
+            .iter()
+            .find(|s| s.ship == ship)
+            .ok_or_else(|| Error::UnknownShip(ship.to_string()))?;
+        let hull_mass = hull_mass.unwrap_or(ship.hull_mass);
+        let generators = generator::derive_generators(
+            ship,
+            hull_mass,
+            grade,
+            &self.generator_modules,
+            &self.blueprint_modifiers,
+            &self.experimental_modifiers,
+        )?;
+
+        if generators.is_empty() {
+            Err(Error::HullTooHeavy(hull_mass))
+        } else {
+            Ok(generators)
+        }
+    }
+
+    /// Every shield booster variant, engineered to the given grade.
+    pub fn engineered_boosters(&self, grade: Grade) -> Result<Vec<ShieldBooster>, Error> {
+        let variants = blueprint::variants(
+            ModuleKind::Booster,
+            &self.blueprint_modifiers,
+            &self.experimental_modifiers,
+        );
+
+        let mut boosters = vec![];
+        for module in self.booster_modules.iter() {
+            for (engineering, experimental) in variants.iter() {
+                let modifiers = blueprint::calculate_modifiers(
+                    ModuleKind::Booster,
+                    engineering,
+                    grade,
+                    experimental,
+                    &self.blueprint_modifiers,
+                    &self.experimental_modifiers,
+                )?;
+
+                boosters.push(engineer_booster(
+                    module,
+                    engineering,
+                    experimental,
+                    grade,
+                    &modifiers,
+                ));
+            }
+        }
+
+        Ok(boosters)
+    }
+
+    /// Sorted list of ships with at least one shield generator.
+    pub fn ships(&self) -> Vec<String> {
+        let mut ships = self
+            .generators
+            .iter()
+            .map(|gen| gen.ship.clone())
+            .chain(self.ships.iter().map(|ship| ship.ship.clone()))
+            .collect::<HashSet<String>>()
+            .into_iter()
+            .collect::<Vec<String>>();
+        ships.sort_unstable();
+        ships
+    }
+
+    /// Minimum and maximum shield class available for a ship.
+    pub fn shield_classes(&self, ship: &str) -> Option<(u8, u8)> {
+        let generators = self.ship_generators(ship, None, Grade::MAX).ok()?;
+        generators.iter().map(|sh| sh.class).minmax().into_option()
+    }
+}
+
+/// Search results along with statistics about the search itself.
+#[derive(Debug, Clone, Serialize)]
+pub struct Outcome {
+    pub shield_class: u8,
+    pub candidate_shields: usize,
+    pub total_shields: usize,
+    pub candidate_boosters: usize,
+    pub total_boosters: usize,
+    pub candidate_pairs: usize,
+    pub total_pairs: usize,
+    pub combinations: usize,
+    pub cell_banks: Vec<CellBank>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hull: Option<HullStat>,
+    #[serde(serialize_with = "serialize_secs")]
+    pub search_time: Duration,
+    /// The best loadouts found, best first
+    pub results: Vec<TestResult>,
+}
+
+impl Outcome {
+    pub fn best(&self) -> Option<&TestResult> {
+        self.results.first()
+    }
+}
+
+fn serialize_secs<S: Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(duration.as_secs_f64())
+}
+
+pub(crate) fn parse_csv<T, R>(s: R) -> Result<Vec<T>, csv::Error>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut reader = csv::Reader::from_reader(s);
+    let mut ret = vec![];
+    for record in reader.deserialize() {
+        let record: T = record?;
+        ret.push(record);
+    }
+    Ok(ret)
+}
+
+fn diminish_res(res: f64) -> f64 {
+    if res < 0.7 {
+        0.7 - (0.7 - res) / 2.0
+    } else {
+        res
+    }
+}
+
+pub fn calculate_booster_stats(boosters: &[&ShieldBooster]) -> BoosterStat {
+    let mut exp_modifier = 1.0;
+    let mut kin_modifier = 1.0;
+    let mut therm_modifier = 1.0;
+    let mut hit_point_bonus = 1.0;
+    let mut power_draw = 0.0;
+
+    for booster in boosters.iter() {
+        exp_modifier *= booster.exp_res_bonus;
+        kin_modifier *= booster.kin_res_bonus;
+        therm_modifier *= booster.therm_res_bonus;
+
+        hit_point_bonus += booster.shield_strength_bonus;
+        power_draw += booster.power_draw;
+    }
+
+    BoosterStat {
+        exp_modifier: diminish_res(exp_modifier),
+        kin_modifier: diminish_res(kin_modifier),
+        therm_modifier: diminish_res(therm_modifier),
+        hit_point_bonus,
+        power_draw,
+    }
+}
+
+pub fn calculate_loadout_stats(shield: &ShieldGenerator, boosters: &BoosterStat) -> LoadoutStat {
+    LoadoutStat {
+        hit_points: boosters.hit_point_bonus * shield.shield_strength,
+        exp_res: shield.exp_res * boosters.exp_modifier,
+        kin_res: shield.kin_res * boosters.kin_modifier,
+        therm_res: shield.therm_res * boosters.therm_modifier,
+        regen_rate: shield.regen_rate,
+        caus_res: shield.caus_res,
+        power_draw: shield.power_draw + boosters.power_draw,
+    }
+}
+
+/// Damage per second the shield takes while the attacker is hitting it.
+///
+/// Damage types which land less often than the attacker is on target are
+/// scaled down by their share of the time under fire.
+pub fn calculate_shield_dps(test: &Scenario, loadout: &LoadoutStat) -> f64 {
+    let under_fire = test.time_under_fire();
+    if under_fire <= 0.0 {
+        return 0.0;
+    }
+
+    let [exp_eff, kin_eff, therm_eff, abs_eff, caus_eff] = test.effectiveness();
+    test.explosive_dps * (exp_eff / under_fire) * loadout.exp_res
+        + test.kinetic_dps * (kin_eff / under_fire) * loadout.kin_res
+        + test.thermal_dps * (therm_eff / under_fire) * loadout.therm_res
+        + test.absolute_dps * (abs_eff / under_fire)
+        + test.caustic_dps * (caus_eff / under_fire) * loadout.caus_res
+}
+
+pub fn calculate_actual_dps(test: &Scenario, loadout: &LoadoutStat) -> f64 {
+    test.time_under_fire() * calculate_shield_dps(test, loadout)
+        - loadout.regen_rate * test.time_regenerating()
+}
+
+/// Mj Shield Cell Banks can restore to a loadout over a fight.
+pub fn calculate_cell_mj(test: &Scenario, cell_banks: &[CellBank], loadout: &LoadoutStat) -> f64 {
+    test.shield_cell_mj
+        + cell_banks
+            .iter()
+            .map(|bank| bank.usable_mj(loadout.hit_points))
+            .sum::<f64>()
+}
+
+/// MW drawn by the fitted Shield Cell Banks.
+pub fn calculate_cell_power(cell_banks: &[CellBank]) -> f64 {
+    cell_banks.iter().map(|bank| bank.power_draw).sum()
+}
+
+pub fn calculate_regen_time(loadout: &LoadoutStat) -> f64 {
+    (loadout.hit_points / 2.0) / loadout.regen_rate
+}
+
+/// Would a loadout with the given drain rate, survival time and stats beat `r`
+/// under the scenario's objective?
+fn beats(
+    scenario: &Scenario,
+    actual_dps: f64,
+    survival_time: f64,
+    stats: &LoadoutStat,
+    r: &TestResult,
+) -> bool {
+    if scenario.objective != Objective::Survival {
+        let score = objective::score(scenario, actual_dps, survival_time, stats);
+        let r_score = objective::score(scenario, r.actual_dps, r.survival_time, &r.stats);
+        if score != r_score {
+            return score > r_score;
+        }
+    }
+
+    if actual_dps < 0.0 {
+        // Regen exceeds effective DPS, favour better regen,
+        // followed by the highest hitpoints.
+        actual_dps < r.actual_dps
+            || ((actual_dps - r.actual_dps).abs() < f64::EPSILON
+                && stats.hit_points > r.stats.hit_points)
+    } else {
+        r.actual_dps > 0.0 && survival_time > r.survival_time
+    }
+}
+
+/// Order results by how well they perform under the scenario's objective, best first.
+pub fn compare_results(scenario: &Scenario, a: &TestResult, b: &TestResult) -> Ordering {
+    if beats(scenario, a.actual_dps, a.survival_time, &a.stats, b) {
+        Ordering::Less
+    } else if beats(scenario, b.actual_dps, b.survival_time, &b.stats, a) {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    }
+}
+
+/// Heap entry ordered so the worst result sits at the top.
+struct Ranked<'a>(TestResult, &'a Scenario);
+
+impl PartialEq for Ranked<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked<'_> {}
+
+impl PartialOrd for Ranked<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_results(self.1, &self.0, &other.0)
+    }
+}
+
+/// Filter the booster list using Jamie van den Berge's algorithm:
+///
+/// Take each pair of booster, plot their values in a kdtree (effectively
+/// modelling a 4-dimensional booster-space), and use that to find pairs which
+/// will always be beaten by other pairs on all dimensions.
+///
+/// The combinations algorithm then only returns results to test which consist
+/// of these pairs.  Returns the surviving pairs and the total number of pairs.
+///
+/// Pairs only compete with others of the same ratings, since a higher rated
+/// pair would otherwise beat every pair using a lower rated booster.
+pub fn filter_booster_pairs(
+    boosters: &[ShieldBooster],
+    disable_filter: bool,
+) -> (Vec<(ShieldBooster, ShieldBooster)>, usize) {
+    let pairs: Vec<Vec<&ShieldBooster>> =
+        boosters.iter().combinations_with_replacement(2).collect();
+    let total_pairs = pairs.len();
+
+    if disable_filter {
+        let pairs = pairs
+            .into_iter()
+            .map(|p| (p[0].clone(), p[1].clone()))
+            .collect();
+        return (pairs, total_pairs);
+    }
+
+    let pair_metrics: Vec<_> = pairs
+        .iter()
+        .map(|pair| {
+            let exp_res = pair[0].exp_res_bonus * pair[1].exp_res_bonus;
+            let kin_res = pair[0].kin_res_bonus * pair[1].kin_res_bonus;
+            let therm_res = pair[0].therm_res_bonus * pair[1].therm_res_bonus;
+            let shield_strength_bonus =
+                pair[0].shield_strength_bonus + pair[1].shield_strength_bonus;
+            vec![-exp_res, -therm_res, -kin_res, shield_strength_bonus]
+        })
+        .collect();
+
+    let rating_pairs: Vec<(char, char)> = pairs
+        .iter()
+        .map(|pair| {
+            let (a, b) = (pair[0].rating, pair[1].rating);
+            (a.min(b), a.max(b))
+        })
+        .unique()
+        .collect();
+
+    let mut filtered_pairs = vec![];
+    for &(a, b) in rating_pairs.iter() {
+        let ids: Vec<usize> = (0..pairs.len())
+            .filter(|&id| {
+                let (x, y) = (pairs[id][0].rating, pairs[id][1].rating);
+                (x.min(y), x.max(y)) == (a, b)
+            })
+            .collect();
+        let metrics: Vec<Vec<f64>> = ids.iter().map(|&id| pair_metrics[id].clone()).collect();
+
+        filtered_pairs.extend(
+            kdtree::non_dominated(&metrics)
+                .into_iter()
+                .map(|i| (pairs[ids[i]][0].clone(), pairs[ids[i]][1].clone())),
+        );
+    }
+
+    (filtered_pairs, total_pairs)
+}
+
+/// Call `task` with each selection of boosters the scenario can fit, made of
+/// the `pinned` boosters and allowed pairs, and respecting its limits on each
+/// rating and booster.
+pub(crate) fn booster_selections<'a, P>(
+    scenario: &Scenario,
+    boosters: &'a [ShieldBooster],
+    pinned: &'a [ShieldBooster],
+    allowed_pairs: &[(ShieldBooster, ShieldBooster)],
+    mut task: P,
+) where
+    P: FnMut(&[&'a ShieldBooster]),
+{
+    let ratings = scenario.booster_rating_limits();
+    let limits: Vec<usize> = ratings
+        .iter()
+        .map(|&(rating, count)| {
+            count.saturating_sub(pinned.iter().filter(|b| b.rating == rating).count())
+        })
+        .collect();
+    let free = scenario
+        .booster_count()
+        .min(8)
+        .saturating_sub(pinned.len().min(8));
+
+    let mut selection: Vec<&ShieldBooster> = pinned.iter().collect();
+    if free == 0 {
+        task(&selection[..]);
+        return;
+    }
+
+    combinations::unique_capped_selections_from_pairs(
+        boosters,
+        allowed_pairs,
+        free,
+        &filter::booster_caps(scenario, boosters, pinned),
+        |booster| {
+            ratings
+                .iter()
+                .position(|&(rating, _)| rating == booster.rating)
+                .unwrap_or(0)
+        },
+        &limits,
+        |boosters| {
+            selection.truncate(pinned.len());
+            selection.extend_from_slice(boosters);
+            task(&selection[..]);
+        },
+    )
+}
+
+/// The shields and boosters a scenario allows, before any pair filtering.
+#[derive(Debug, Clone)]
+pub struct Candidates {
+    pub shield_class: u8,
+    pub generators: Vec<ShieldGenerator>,
+    pub total_shields: usize,
+    pub boosters: Vec<ShieldBooster>,
+    pub total_boosters: usize,
+    /// Boosters every selection includes
+    pub pinned: Vec<ShieldBooster>,
+}
+
+impl Candidates {
+    /// Select candidates for a scenario.  If `damage_filter` is set, boosters
+    /// specialising in damage types the attacker doesn't use are dropped.
+    pub fn select(scenario: &Scenario, db: &Database, damage_filter: bool) -> Result<Self, Error> {
+        let ratings = scenario.booster_rating_limits();
+        let booster_grade = Grade::new(scenario.booster_grade, scenario.booster_roll)?;
+        let shield_grade = Grade::new(scenario.shield_grade, scenario.shield_roll)?;
+
+        let engineered;
+        let all_boosters = if let Some(ref inventory) = scenario.inventory {
+            engineered = inventory.boosters(db)?;
+            &engineered
+        } else if booster_grade.is_max() {
+            &db.boosters
+        } else {
+            engineered = db.engineered_boosters(booster_grade)?;
+            &engineered
+        };
+        let (generator_modules, booster_modules) = match scenario.inventory {
+            Some(_) => ("owned shield generators", "owned shield boosters"),
+            None => ("shield generators", "shield boosters"),
+        };
+
+        let total_boosters = all_boosters.len();
+        let filter = ModuleFilter::new(scenario, damage_filter);
+        let pinned = filter::pinned_boosters(scenario, all_boosters)?;
+        let boosters: Vec<ShieldBooster> = all_boosters
+            .iter()
+            .filter(|booster| ratings.iter().any(|&(rating, _)| booster.rating == rating))
+            .filter(|booster| filter.allows_booster(booster))
+            .cloned()
+            .collect();
+        if boosters.len() > combinations::MAX_ITEMS {
+            return Err(Error::TooManyBoosters(boosters.len()));
+        }
+
+        for &(rating, count) in ratings.iter() {
+            if !db.boosters.iter().any(|booster| booster.rating == rating) {
+                return Err(Error::InvalidBoosterRating(rating));
+            }
+            let free = count.saturating_sub(pinned.iter().filter(|b| b.rating == rating).count());
+            let available: usize = boosters
+                .iter()
+                .zip(filter::booster_caps(scenario, &boosters, &pinned))
+                .filter(|(booster, _)| booster.rating == rating)
+                .map(|(_, cap)| cap.min(free))
+                .sum();
+            if available < free {
+                return Err(Error::NoCandidates(booster_modules));
+            }
+        }
+
+        let mut generators =
+            db.ship_generators(&scenario.ship, scenario.hull_mass, shield_grade)?;
+
+        let (min_gen, max_gen) = generators
+            .iter()
+            .map(|sh| sh.class)
+            .minmax()
+            .into_option()
+            .ok_or_else(|| Error::UnknownShip(scenario.ship.clone()))?;
+
+        if let Some(ref inventory) = scenario.inventory {
+            generators = inventory.generators(db, &scenario.ship, scenario.hull_mass)?;
+        }
+        let total_shields = generators.len();
+
+        // Without an owned generator the ship takes, the class check passes
+        // and there's nothing to search
+        let largest = generators
+            .iter()
+            .map(|sh| sh.class)
+            .max()
+            .unwrap_or(max_gen);
+        let shield_class = scenario.shield_class.unwrap_or(largest);
+
+        if shield_class < min_gen || shield_class > max_gen {
+            return Err(Error::InvalidShieldClass {
+                class: shield_class,
+                min: min_gen,
+                max: max_gen,
+            });
+        }
+
+        let generators: Vec<ShieldGenerator> = generators
+            .into_iter()
+            .filter(|shield| shield.class == shield_class)
+            .filter(|shield| !(scenario.disable_prismatic && shield.kind == "Prismatic"))
+            .filter(|shield| filter.allows_shield(shield))
+            .collect();
+
+        if generators.is_empty() {
+            return Err(Error::NoCandidates(generator_modules));
+        }
+
+        Ok(Self {
+            shield_class,
+            generators,
+            total_shields,
+            boosters,
+            total_boosters,
+            pinned,
+        })
+    }
+
+    /// Drop generators the power plant can't run alongside the pinned
+    /// boosters, the cheapest boosters of each rating for the other slots and
+    /// the Shield Cell Banks, if the scenario gives a power budget.
+    pub fn limit_power(
+        &mut self,
+        scenario: &Scenario,
+        cell_banks: &[CellBank],
+    ) -> Result<(), Error> {
+        let available = match scenario.power_budget() {
+            Some(available) => available,
+            None => return Ok(()),
+        };
+
+        let boosters: f64 = scenario
+            .booster_rating_limits()
+            .iter()
+            .map(|&(rating, count)| {
+                let pinned = self.pinned.iter().filter(|b| b.rating == rating);
+                let free = count.saturating_sub(pinned.clone().count());
+                let cheapest = self
+                    .boosters
+                    .iter()
+                    .filter(|booster| booster.rating == rating)
+                    .map(|booster| booster.power_draw)
+                    .fold(f64::INFINITY, f64::min);
+                pinned.map(|b| b.power_draw).sum::<f64>()
+                    + if free > 0 {
+                        cheapest * free as f64
+                    } else {
+                        0.0
+                    }
+            })
+            .sum();
+        let others = boosters + calculate_cell_power(cell_banks);
+
+        let required = self
+            .generators
+            .iter()
+            .map(|shield| shield.power_draw)
+            .fold(f64::INFINITY, f64::min)
+            + others;
+        if required > available {
+            return Err(Error::InsufficientPower {
+                required,
+                available,
+            });
+        }
+
+        self.generators
+            .retain(|shield| shield.power_draw + others <= available);
+        Ok(())
+    }
+}
+
+/// Search every candidate loadout for the one which survives longest.
+pub fn optimise(scenario: &Scenario, db: &Database) -> Result<Outcome, Error> {
+    let cell_banks = db.resolve_cell_banks(&scenario.cell_banks)?;
+    let mut candidates = Candidates::select(scenario, db, true)?;
+    candidates.limit_power(scenario, &cell_banks)?;
+    let (filtered_pairs, total_pairs) =
+        filter_booster_pairs(&candidates.boosters, !scenario.prefilter());
+
+    search(scenario, db, &candidates, &filtered_pairs, total_pairs)
+}
+
+/// Search loadouts of the candidates made of the given booster pairs.
+///
+/// Booster pairs are filtered without regard to the attacker, so they can be
+/// reused for any attacker with the same modules.
+pub(crate) fn search(
+    scenario: &Scenario,
+    db: &Database,
+    candidates: &Candidates,
+    filtered_pairs: &[(ShieldBooster, ShieldBooster)],
+    total_pairs: usize,
+) -> Result<Outcome, Error> {
+    let cell_banks = db.resolve_cell_banks(&scenario.cell_banks)?;
+    let hull = db.resolve_hull(scenario)?;
+    let cell_power = calculate_cell_power(&cell_banks);
+    let power_budget = scenario.power_budget();
+
+    let Candidates {
+        shield_class,
+        ref generators,
+        total_shields,
+        ref boosters,
+        total_boosters,
+        ref pinned,
+    } = *candidates;
+
+    let top = scenario.top.max(1);
+    let mut results: BinaryHeap<Ranked> = BinaryHeap::with_capacity(top + 1);
+    let mut tally = Tally::new(scenario);
+
+    let mut loadouts = 0;
+    let start = Instant::now();
+
+    booster_selections(
+        scenario,
+        boosters,
+        pinned,
+        filtered_pairs,
+        |booster_loadout| {
+            let booster_stat = calculate_booster_stats(booster_loadout);
+            for shield in generators.iter() {
+                loadouts += 1;
+                let mut stats = calculate_loadout_stats(shield, &booster_stat);
+                // These increase regen time (according to coriolis), and do not stack with boosters
+                stats.hit_points += scenario.reinforced_mj;
+                stats.power_draw += cell_power;
+
+                if scenario
+                    .regen_time_limit
+                    .map(|limit| calculate_regen_time(&stats) > limit)
+                    .unwrap_or(false)
+                    || power_budget
+                        .map(|budget| stats.power_draw > budget)
+                        .unwrap_or(false)
+                    || !tally.allows(scenario, &stats)
+                {
+                    continue;
+                }
+
+                let actual_dps = calculate_actual_dps(scenario, &stats);
+                let survival_time = (stats.hit_points
+                    + calculate_cell_mj(scenario, &cell_banks, &stats))
+                    / actual_dps;
+
+                let better = results.len() < top
+                    || results
+                        .peek()
+                        .map(|worst| beats(scenario, actual_dps, survival_time, &stats, &worst.0))
+                        .unwrap_or(true);
+
+                if better {
+                    results.push(Ranked(
+                        TestResult {
+                            actual_dps,
+                            survival_time,
+                            time_to_kill: hull
+                                .as_ref()
+                                .map(|hull| hull::calculate_time_to_kill(survival_time, hull)),
+                            shield: shield.clone(),
+                            boosters: booster_loadout.iter().cloned().cloned().collect(),
+                            stats,
+                        },
+                        scenario,
+                    ));
+
+                    if results.len() > top {
+                        results.pop();
+                    }
+                }
+            }
+        },
+    );
+
+    if results.is_empty() {
+        if let Some(e) = tally.infeasible() {
+            return Err(e);
+        }
+    }
+
+    Ok(Outcome {
+        shield_class,
+        candidate_shields: generators.len(),
+        total_shields,
+        candidate_boosters: boosters.len(),
+        total_boosters,
+        candidate_pairs: filtered_pairs.len(),
+        total_pairs,
+        combinations: loadouts,
+        cell_banks,
+        hull,
+        search_time: start.elapsed(),
+        results: results
+            .into_sorted_vec()
+            .into_iter()
+            .map(|ranked| ranked.0)
+            .collect(),
+    })
+}
+
+#[test]
+fn test_optimise_builtin() {
+    let db = Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Imperial Cutter".to_string(),
+        shield_class: Some(7),
+        shield_booster_count: 6,
+        kinetic_dps: 30.0,
+        thermal_dps: 40.0,
+        damage_effectiveness: 0.6,
+        regen_time_limit: Some(240.0),
+        ..Scenario::default()
+    };
+
+    let outcome = optimise(&scenario, &db).unwrap();
+    let best = outcome.best().unwrap();
+    assert_eq!(outcome.candidate_shields, 45);
+    assert_eq!(best.shield.kind, "Bi-Weave");
+    assert_eq!(best.boosters.len(), 6);
+    assert!((best.survival_time - 134.4).abs() < 0.1);
+
+    // More boosters than the selection bitmasks hold is an error, not a panic
+    let mut crowded = db.clone();
+    for _ in 0..5 {
+        crowded.boosters.extend(db.boosters.iter().cloned());
+    }
+    let unfiltered = Scenario {
+        disable_filter: true,
+        ..scenario
+    };
+    assert!(matches!(
+        optimise(&unfiltered, &crowded),
+        Err(Error::TooManyBoosters(_))
+    ));
+}
+
+#[test]
+fn test_optimise_top() {
+    let db = Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        shield_booster_count: 3,
+        explosive_dps: 20.0,
+        kinetic_dps: 30.0,
+        thermal_dps: 40.0,
+        top: 5,
+        ..Scenario::default()
+    };
+
+    let outcome = optimise(&scenario, &db).unwrap();
+    let best = optimise(&Scenario { top: 1, ..scenario }, &db).unwrap();
+    assert_eq!(outcome.results.len(), 5);
+    assert!((outcome.results[0].survival_time - best.results[0].survival_time).abs() < 1e-9);
+    for pair in outcome.results.windows(2) {
+        assert!(pair[0].survival_time >= pair[1].survival_time);
+    }
+}
+
+#[test]
+fn test_power_budget() {
+    let db = Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Imperial Cutter".to_string(),
+        shield_booster_count: 8,
+        kinetic_dps: 30.0,
+        thermal_dps: 40.0,
+        ..Scenario::default()
+    };
+
+    let best = optimise(&scenario, &db).unwrap().results.remove(0);
+    assert_eq!(best.shield.kind, "Prismatic");
+    // 8A Prismatic and eight A-rated boosters
+    assert!((best.stats.power_draw - (8.4 + 8.0 * 1.2)).abs() < 1e-9);
+
+    let limited = Scenario {
+        power_available: Some(30.0),
+        power_used: 14.0,
+        ..scenario.clone()
+    };
+    let outcome = optimise(&limited, &db).unwrap();
+    let best = outcome.best().unwrap();
+    assert_ne!(best.shield.kind, "Prismatic");
+    assert!(best.stats.power_draw <= 16.0);
+
+    let starved = Scenario {
+        power_available: Some(20.0),
+        ..limited
+    };
+    assert!(matches!(
+        optimise(&starved, &db),
+        Err(Error::InsufficientPower { .. })
+    ));
+}
+
+#[test]
+fn test_effectiveness() {
+    let loadout = LoadoutStat {
+        hit_points: 1000.0,
+        regen_rate: 10.0,
+        exp_res: 1.0,
+        kin_res: 0.5,
+        therm_res: 0.8,
+        caus_res: 1.0,
+        power_draw: 0.0,
+    };
+    let scenario = Scenario {
+        kinetic_dps: 100.0,
+        thermal_dps: 50.0,
+        damage_effectiveness: 0.5,
+        ..Scenario::default()
+    };
+    // 0.5 * (50 + 40) - 10 * 0.5
+    assert!((calculate_actual_dps(&scenario, &loadout) - 40.0).abs() < 1e-9);
+
+    let scenario = Scenario {
+        thermal_effectiveness: Some(0.9),
+        ..scenario
+    };
+    assert!((scenario.time_under_fire() - 0.9).abs() < 1e-9);
+    // 0.5 * 50 + 0.9 * 40 - 10 * 0.1
+    assert!((calculate_actual_dps(&scenario, &loadout) - 60.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_mixed_booster_ratings() {
+    let db = Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        booster_ratings: vec!['C', 'a', 'C'],
+        kinetic_dps: 100.0,
+        ..Scenario::default()
+    };
+    assert_eq!(scenario.booster_count(), 3);
+    assert_eq!(scenario.booster_rating_limits(), vec![('A', 1), ('C', 2)]);
+
+    let outcome = optimise(&scenario, &db).unwrap();
+    let ratings: Vec<char> = outcome
+        .best()
+        .unwrap()
+        .boosters
+        .iter()
+        .map(|b| b.rating)
+        .sorted()
+        .collect();
+    assert_eq!(ratings, vec!['A', 'C', 'C']);
+
+    // Lower rated pairs survive the pair filter alongside A-rated ones
+    let (pairs, _) = filter_booster_pairs(
+        &Candidates::select(&scenario, &db, false).unwrap().boosters,
+        false,
+    );
+    assert!(pairs
+        .iter()
+        .any(|(a, b)| a.rating == 'C' && b.rating == 'C'));
+}
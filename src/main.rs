@@ -1,422 +1,615 @@
 use std::error::Error;
-use std::io::Read;
+use std::fs::File;
 use std::path::PathBuf;
-use std::collections::HashSet;
+use std::str::FromStr;
 
 use itertools::Itertools;
-use serde::de::DeserializeOwned;
-use serde::Deserialize;
+use serde::Serialize;
 use structopt::StructOpt;
 
-mod combinations;
-mod kdtree;
-
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct ShieldGenerator {
-    ship: String,
-    class: u8,
-    #[serde(rename = "Type")]
-    kind: String,
-    engineering: String,
-    experimental: String,
-    shield_strength: f64,
-    regen_rate: f64,
-    exp_res: f64,
-    kin_res: f64,
-    therm_res: f64,
-}
+use elite_shield_tester::allocation::{self, Allocation, UtilitySpec};
+use elite_shield_tester::blueprint::Grade;
+use elite_shield_tester::constraint;
+use elite_shield_tester::evaluate::{self, BoosterSpec, ShieldSpec};
+use elite_shield_tester::filter;
+use elite_shield_tester::fitting::{self, Fitting, SlotModule};
+use elite_shield_tester::hull::HullStat;
+use elite_shield_tester::inventory::Inventory;
+use elite_shield_tester::journal::{self, Loadout};
+use elite_shield_tester::objective::{self, Objective};
+use elite_shield_tester::pareto::pareto_front;
+use elite_shield_tester::simulation::{simulate, Simulation, SimulationResult};
+use elite_shield_tester::sweep::{self, Sweep};
+use elite_shield_tester::upgrade::{self, Change, UpgradeStep};
+use elite_shield_tester::weapon::Weapon;
+use elite_shield_tester::{
+    calculate_cell_mj, calculate_regen_time, optimise, Database, Outcome, Scenario, ShieldBooster,
+    TestResult,
+};
 
-#[derive(Debug, Clone, PartialEq, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-struct ShieldBooster {
-    rating: char,
-    engineering: String,
-    experimental: String,
-    shield_strength_bonus: f64,
-    exp_res_bonus: f64,
-    kin_res_bonus: f64,
-    therm_res_bonus: f64,
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    Text,
+    Json,
 }
 
-#[derive(Debug, Clone)]
-struct LoadoutStat {
-    hit_points: f64,
-    regen_rate: f64,
-    exp_res: f64,
-    kin_res: f64,
-    therm_res: f64,
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Unknown format: {} (expected text or json)", s)),
+        }
+    }
 }
 
-#[derive(Debug, Clone, StructOpt)]
+#[derive(Debug, Clone, StructOpt, Serialize)]
 #[structopt(
     name = "elite_shield_tester",
     about = "Elite Dangerous Shield Optimiser"
 )]
 struct TestConfig {
-    /// Number of shield boosters to fit
-    #[structopt(short, long, default_value = "1")]
-    shield_booster_count: usize,
-    /// Shield booster rating, A-E
-    #[structopt(long, default_value = "A")]
-    shield_booster_rating: char,
-    /// Explosive damage per second
-    #[structopt(short, long, default_value = "0")]
-    explosive_dps: f64,
-    /// Kinetic damage per second
-    #[structopt(short, long, default_value = "0")]
-    kinetic_dps: f64,
-    /// Thermal damage per second
-    #[structopt(short, long, default_value = "0")]
-    thermal_dps: f64,
-    /// Absolute damage per second
-    #[structopt(short, long, default_value = "0")]
-    absolute_dps: f64,
-    /// Attacker shot success ratio, 0-1
-    #[structopt(short, long, default_value = "0.5")]
-    damage_effectiveness: f64,
-    /// Mj available via Shield Cell Banks
-    #[structopt(long, default_value = "0")]
-    shield_cell_mj: f64,
-    /// Mj provided by Guardian Shield Reinforcements
-    #[structopt(long, default_value = "0")]
-    reinforced_mj: f64,
-    /// Filter out prismatic shields
-    #[structopt(long)]
-    disable_prismatic: bool,
-    /// Disregard shields that take longer than this many seconds to regenerate from 50%
-    #[structopt(long)]
-    regen_time_limit: Option<f64>,
-    /// Require experimental effects
-    #[structopt(long)]
-    force_experimental: bool,
-    /// Disable pre-filtering (debugging)
-    #[structopt(long)]
-    disable_filter: bool,
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    scenario: Scenario,
     /// Override default shield list
     #[structopt(long)]
     shield_csv: Option<PathBuf>,
     /// Override default booster list
     #[structopt(long)]
     booster_csv: Option<PathBuf>,
-    /// Ship name
-    #[structopt(long, default_value = "Anaconda")]
-    ship: String,
-    /// Shield class (default: maximum possible)
+    /// Override default Shield Cell Bank list
+    #[structopt(long)]
+    scb_csv: Option<PathBuf>,
+    /// Take the ship, shield class and boosters from the latest Loadout event
+    /// in this journal directory
+    #[structopt(long, conflicts_with = "loadout-json")]
+    journal: Option<PathBuf>,
+    /// Take the ship, shield class and boosters from a saved Loadout event
+    #[structopt(long)]
+    loadout_json: Option<PathBuf>,
+    /// Cap boosters as listed in this file, one per line in the form
+    /// --booster-cap takes
+    #[structopt(long = "booster-caps")]
+    booster_caps_file: Option<PathBuf>,
+    /// Search only the shield generators and boosters listed in this inventory
+    /// CSV file, each fitted at most as many times as it's owned
+    #[structopt(long, conflicts_with = "stored-modules")]
+    inventory: Option<PathBuf>,
+    /// Search only the shield generators and boosters in storage or fitted,
+    /// from the latest StoredModules and Loadout events in this journal
+    /// directory
+    #[structopt(long)]
+    stored_modules: Option<PathBuf>,
+    /// Evaluate the loadout given by --shield and --booster, or imported from
+    /// the journal, and compare it with the optimum
+    #[structopt(long, conflicts_with = "pareto")]
+    evaluate: bool,
+    /// List the module changes from the loadout given by --shield and --booster,
+    /// or imported from the journal, to the optimum, best first
+    #[structopt(long, conflicts_with = "pareto")]
+    upgrade_path: bool,
+    /// Current shield generator, e.g. "Prismatic,Reinforced,Hi-Cap"
     #[structopt(long)]
-    shield_class: Option<u8>,
+    shield: Option<ShieldSpec>,
+    /// Current shield booster, e.g. "Heavy Duty,Super Capacitors" or
+    /// "C,Resistance Augmented" (repeatable)
+    #[structopt(long = "booster", number_of_values = 1)]
+    boosters: Vec<BoosterSpec>,
+    /// Work out how many of this many utility slots to give to shield
+    /// boosters, rather than using --shield-booster-count
+    #[structopt(long, conflicts_with_all = &["pareto", "evaluate", "upgrade-path"])]
+    utility_slots: Option<usize>,
+    /// Another utility wanting a slot, e.g. "Point Defence" if required, or
+    /// "Heat Sink,20" if optional and worth 20 seconds of survival (repeatable)
+    #[structopt(long = "utility", number_of_values = 1)]
+    utilities: Vec<UtilitySpec>,
+    /// Sizes of the optional internal slots, e.g. "6,5,5,4,3", to choose the
+    /// shield class, SCBs, Guardian Shield Reinforcements and HRPs
+    #[structopt(
+        long,
+        use_delimiter = true,
+        conflicts_with_all = &["pareto", "evaluate", "upgrade-path", "utility-slots"]
+    )]
+    internal_slots: Vec<u8>,
+    /// List every loadout not beaten on hitpoints, resistances and regen
+    #[structopt(long)]
+    pareto: bool,
+    /// Find the best loadout for every mix of the total explosive, kinetic,
+    /// thermal and absolute DPS, in shares of 1/steps of the total
+    #[structopt(
+        long,
+        value_name = "steps",
+        conflicts_with_all = &["pareto", "evaluate", "upgrade-path", "utility-slots", "internal-slots"]
+    )]
+    sweep: Option<usize>,
+    /// Write the sweep to this CSV file
+    #[structopt(long, requires = "sweep")]
+    sweep_csv: Option<PathBuf>,
+    /// Output format, text or json
+    #[structopt(long, default_value = "text")]
+    format: Format,
+    /// Simulate a fight against each result, reporting when its shield collapses
+    #[structopt(long)]
+    simulate: bool,
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    simulation: Simulation,
 }
 
-#[derive(Debug, Clone)]
-struct TestResult {
-    actual_dps: f64,
-    survival_time: f64,
-    shield: ShieldGenerator,
-    boosters: Vec<ShieldBooster>,
-    stats: LoadoutStat,
+#[derive(Debug, Serialize)]
+struct SweepReport<'a> {
+    version: &'static str,
+    config: &'a TestConfig,
+    #[serde(flatten)]
+    sweep: &'a Sweep,
 }
 
-fn parse_csv<T, R>(s: R) -> Result<Vec<T>, csv::Error>
-where
-    R: Read,
-    T: DeserializeOwned,
-{
-    let mut reader = csv::Reader::from_reader(s);
-    let mut ret = vec![];
-    for record in reader.deserialize() {
-        let record: T = record?;
-        ret.push(record);
-    }
-    Ok(ret)
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    version: &'static str,
+    config: &'a TestConfig,
+    #[serde(flatten)]
+    outcome: &'a Outcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    loadout: Option<Loadout>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    evaluation: Option<TestResult>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    upgrade_path: Vec<UpgradeStep>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    allocations: Vec<Allocation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fitting: Option<Fitting>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    weapons: Vec<Weapon>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    simulations: Vec<SimulationResult>,
 }
 
-fn diminish_res(res: f64) -> f64 {
-    if res < 0.7 {
-        0.7 - (0.7 - res) / 2.0
-    } else {
-        res
-    }
-}
+fn main() -> Result<(), Box<dyn Error>> {
+    let config = TestConfig::from_args();
+    let text = config.format == Format::Text;
 
-#[derive(Debug, Clone)]
-struct BoosterStat {
-    exp_modifier: f64,
-    kin_modifier: f64,
-    therm_modifier: f64,
-    hit_point_bonus: f64,
-}
+    if text {
+        println!(
+            "Elite Shield Tester Rust Edition v{}",
+            env!("CARGO_PKG_VERSION")
+        );
+    }
 
-fn calculate_booster_stats(boosters: &[&ShieldBooster]) -> BoosterStat {
-    let mut exp_modifier = 1.0;
-    let mut kin_modifier = 1.0;
-    let mut therm_modifier = 1.0;
-    let mut hit_point_bonus = 1.0;
+    let mut db = Database::builtin()?;
 
-    for booster in boosters.iter() {
-        exp_modifier *= booster.exp_res_bonus;
-        kin_modifier *= booster.kin_res_bonus;
-        therm_modifier *= booster.therm_res_bonus;
+    if let Some(ref path) = config.shield_csv {
+        if text {
+            println!("Custom Shield CSV: {}", path.display());
+        }
+        db.load_shields_file(path)?;
+    }
 
-        hit_point_bonus += booster.shield_strength_bonus;
+    if let Some(ref path) = config.booster_csv {
+        if text {
+            println!("Custom Booster CSV: {}", path.display());
+        }
+        db.load_boosters_file(path)?;
     }
 
-    BoosterStat {
-        exp_modifier: diminish_res(exp_modifier),
-        kin_modifier: diminish_res(kin_modifier),
-        therm_modifier: diminish_res(therm_modifier),
-        hit_point_bonus,
+    if let Some(ref path) = config.scb_csv {
+        if text {
+            println!("Custom SCB CSV: {}", path.display());
+        }
+        db.load_cell_banks_file(path)?;
     }
-}
 
-fn calculate_loadout_stats(shield: &ShieldGenerator, boosters: &BoosterStat) -> LoadoutStat {
-    LoadoutStat {
-        hit_points: boosters.hit_point_bonus * shield.shield_strength,
-        exp_res: shield.exp_res * boosters.exp_modifier,
-        kin_res: shield.kin_res * boosters.kin_modifier,
-        therm_res: shield.therm_res * boosters.therm_modifier,
-        regen_rate: shield.regen_rate,
+    let weapons = match db.resolve_weapons(&config.scenario.weapons) {
+        Ok(weapons) => weapons,
+        Err(e @ elite_shield_tester::Error::UnknownWeapon(_))
+        | Err(e @ elite_shield_tester::Error::UnknownBlueprint(_)) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut test = config.scenario.with_weapons(&weapons);
+
+    if let Some(ref name) = config.scenario.thargoid {
+        match db.thargoid(name) {
+            Ok(profile) => test = test.with_thargoid(profile),
+            Err(e) => {
+                println!("{}", e);
+                println!("Known Thargoids:");
+                for profile in db.thargoids.iter() {
+                    println!(" * {}", profile.name);
+                }
+                std::process::exit(1);
+            }
+        }
     }
-}
 
-fn calculate_actual_dps(test: &TestConfig, loadout: &LoadoutStat) -> f64 {
-    test.damage_effectiveness
-        * (test.explosive_dps * loadout.exp_res
-            + test.kinetic_dps * loadout.kin_res
-            + test.thermal_dps * loadout.therm_res
-            + test.absolute_dps)
-        - loadout.regen_rate * (1.0 - test.damage_effectiveness)
-}
+    let event = match (&config.journal, &config.loadout_json) {
+        (Some(dir), _) => Some(journal::read_journal_loadout(dir)),
+        (_, Some(path)) => Some(journal::read_loadout_json(path)),
+        _ => None,
+    };
+    let loadout = match event.map(|event| db.resolve_loadout(&event?, test.hull_mass)) {
+        Some(Ok(loadout)) => {
+            test = test.with_loadout(&loadout);
+            Some(loadout)
+        }
+        Some(Err(e)) => {
+            println!("Can't import loadout: {}", e);
+            std::process::exit(1);
+        }
+        None => None,
+    };
 
-fn calculate_regen_time(loadout: &LoadoutStat) -> f64 {
-    (loadout.hit_points / 2.0) / loadout.regen_rate
-}
+    if let Some(ref path) = config.booster_caps_file {
+        match filter::read_booster_caps(path) {
+            Ok(caps) => test.booster_caps.extend(caps),
+            Err(e) => {
+                println!("Can't read booster caps: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let test = TestConfig::from_args();
+    let inventory = match (&config.inventory, &config.stored_modules) {
+        (Some(path), _) => Some(Inventory::read_csv(path)),
+        (_, Some(dir)) => Some(Inventory::read_journal(dir)),
+        _ => None,
+    };
+    match inventory {
+        Some(Ok(inventory)) => test.inventory = Some(inventory),
+        Some(Err(e)) => {
+            println!("Can't read inventory: {}", e);
+            std::process::exit(1);
+        }
+        None => (),
+    }
 
-    println!(
-        "Elite Shield Tester Rust Edition v{}",
-        env!("CARGO_PKG_VERSION")
-    );
+    let scoring = config.evaluate || config.upgrade_path;
+    if config.shield.is_some() || !config.boosters.is_empty() {
+        if !scoring {
+            println!("--shield and --booster need --evaluate or --upgrade-path");
+            std::process::exit(1);
+        }
+        test.shield_booster_count = config.boosters.len();
+        if config.boosters.iter().any(|b| b.rating.is_some()) {
+            test.booster_ratings = config
+                .boosters
+                .iter()
+                .map(|b| b.rating.unwrap_or(test.shield_booster_rating))
+                .collect();
+        }
+    }
+    if scoring && config.shield.is_none() && loadout.is_none() {
+        println!("--evaluate and --upgrade-path need a --shield, --journal or --loadout-json");
+        std::process::exit(1);
+    }
 
-    let mut generators: Vec<ShieldGenerator> =
-        parse_csv(&include_bytes!("../data/Shields.csv")[..])?;
-    let mut boosters: Vec<ShieldBooster> =
-        parse_csv(&include_bytes!("../data/ShieldBoosterVariants.csv")[..])?;
+    if !config.utilities.is_empty() && config.utility_slots.is_none() {
+        println!("--utility needs --utility-slots");
+        std::process::exit(1);
+    }
+    let allocations = match config.utility_slots {
+        Some(slots) => allocation::allocate(&test, &db, slots, &config.utilities)
+            .map_err(|e| search_error(&db, e))?,
+        None => vec![],
+    };
+    if let Some(best) = allocation::recommend(&allocations) {
+        test.shield_booster_count = best.boosters;
+        test.booster_ratings.clear();
+    }
+
+    let fitting = if config.internal_slots.is_empty() {
+        None
+    } else {
+        match fitting::fit_internals(&test, &db, &config.internal_slots)
+            .map_err(|e| search_error(&db, e))?
+        {
+            Some(fitting) => {
+                test = fitting.apply(&test);
+                Some(fitting)
+            }
+            None => {
+                println!("No way to fill the internal slots meets the power budget, regen time limit and constraints.");
+                std::process::exit(1);
+            }
+        }
+    };
+    let test = &test;
 
-    if let Some(ref path) = test.shield_csv {
-        println!("Custom Shield CSV: {}", path.display());
-        generators = parse_csv(std::fs::File::open(path)?)?;
+    if let Some(steps) = config.sweep {
+        let sweep = sweep::sweep(test, &db, steps).map_err(|e| search_error(&db, e))?;
+        if let Some(ref path) = config.sweep_csv {
+            sweep::write_csv(&sweep, File::create(path)?)?;
+        }
+        if text {
+            print_sweep(test, &sweep);
+        } else {
+            let report = SweepReport {
+                version: env!("CARGO_PKG_VERSION"),
+                config: &config,
+                sweep: &sweep,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        return Ok(());
     }
 
-    if let Some(ref path) = test.booster_csv {
-        println!("Custom Booster CSV: {}", path.display());
-        boosters = parse_csv(std::fs::File::open(path)?)?;
+    let outcome = if config.pareto {
+        pareto_front(test, &db)
+    } else {
+        optimise(test, &db)
+    };
+    let outcome = outcome.map_err(|e| search_error(&db, e))?;
+
+    let current = match (&config.shield, &loadout) {
+        (Some(shield), _) => match evaluate::resolve_loadout(test, &db, shield, &config.boosters) {
+            Ok(current) => Some(current),
+            Err(e @ elite_shield_tester::Error::UnknownShield(_))
+            | Err(e @ elite_shield_tester::Error::UnknownBooster(_)) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+            Err(e) => return Err(e.into()),
+        },
+        (None, Some(loadout)) if scoring => Some(loadout.clone()),
+        _ => None,
+    };
+
+    let evaluation = match current {
+        Some(ref current) if config.evaluate => Some(evaluate::evaluate(test, &db, current)?),
+        _ => None,
+    };
+
+    let upgrade_path = match (&current, outcome.best()) {
+        (Some(current), Some(best)) if config.upgrade_path => {
+            upgrade::upgrade_path(test, &db, current, best)?
+        }
+        _ => vec![],
+    };
+
+    let simulations: Vec<SimulationResult> = if config.simulate {
+        outcome
+            .results
+            .iter()
+            .map(|res| simulate(test, &config.simulation, &outcome.cell_banks, &res.stats))
+            .collect::<Result<_, _>>()
+            .map_err(|e| search_error(&db, e))?
+    } else {
+        vec![]
+    };
+
+    if !text {
+        let report = Report {
+            version: env!("CARGO_PKG_VERSION"),
+            config: &config,
+            outcome: &outcome,
+            loadout,
+            evaluation,
+            upgrade_path,
+            allocations,
+            fitting,
+            weapons,
+            simulations,
+        };
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
     }
 
-    let total_boosters = boosters.len();
-    let boosters: Vec<ShieldBooster> = boosters
-        .into_iter()
-        .filter(|booster| booster.rating == test.shield_booster_rating.to_ascii_uppercase())
-        .filter(|booster| {
-            !test.force_experimental || booster.experimental != "No Experimental Effect"
-        })
-        .filter(|booster| {
-            // Naively filter out irrelevant boosters
-            test.disable_filter
-                || !(test.explosive_dps == 0.0 && (booster.engineering == "Blast Resistance")
-                    || test.kinetic_dps == 0.0 && (booster.engineering == "Kinetic Resistance")
-                    || test.thermal_dps == 0.0 && (booster.engineering == "Thermal Resistance"))
-        })
-        .map(|mut booster| {
-            // Convert resistances to resonances
-            booster.exp_res_bonus = 1.0 - booster.exp_res_bonus;
-            booster.kin_res_bonus = 1.0 - booster.kin_res_bonus;
-            booster.therm_res_bonus = 1.0 - booster.therm_res_bonus;
-            booster
-        })
-        .collect();
+    print_search_setup(&outcome);
 
-    if boosters.is_empty() {
-        println!("Invalid booster rating: {}", test.shield_booster_rating);
-        std::process::exit(1);
+    if config.pareto {
+        print_pareto(test, &outcome);
+        return Ok(());
     }
 
-    let ship_generators: Vec<ShieldGenerator> = generators
-        .iter()
-        .filter(|sh| sh.ship == test.ship)
-        .cloned()
-        .collect();
+    print_test_setup(test, &weapons, loadout.as_ref(), &outcome);
 
-    if ship_generators.is_empty() {
-        println!("Unknown ship: {}", test.ship);
-        println!("Known Ships:");
-        let mut ships = generators
-            .into_iter()
-            .map(|gen| gen.ship)
-            .collect::<HashSet<String>>()
-            .into_iter()
-            .collect::<Vec<String>>();
-        ships.sort_unstable();
-
-        for ship in ships {
-            println!(" * {}", ship);
+    println!();
+    println!("---- TEST RESULTS ----");
+    println!();
+
+    match evaluation.as_ref().or_else(|| outcome.best()) {
+        None => {
+            println!("Nothing useful to report.");
         }
+        Some(res) => print_result(test, res),
+    }
 
-        std::process::exit(1);
+    if let Some(ref hull) = outcome.hull {
+        print_hull(hull);
     }
 
-    let generators = ship_generators;
+    if let Some(ref res) = evaluation {
+        print_comparison(res, &outcome);
+    }
 
-    let min_gen = generators.iter().map(|sh| sh.class).min().unwrap();
-    let max_gen = generators.iter().map(|sh| sh.class).max().unwrap();
+    if let Some(ref current) = current {
+        if config.upgrade_path {
+            print_upgrade_path(test, &db, current, &upgrade_path)?;
+        }
+    }
 
-    let shield_class = test.shield_class.unwrap_or(max_gen);
+    if let Some(slots) = config.utility_slots {
+        print_allocations(slots, &config.utilities, &allocations);
+    }
 
-    if shield_class < min_gen || shield_class > max_gen {
-        println!("Invalid shield class: {}", shield_class);
-        println!("Valid classes: {} - {}", min_gen, max_gen);
-        std::process::exit(1);
+    if let Some(ref fitting) = fitting {
+        print_fitting(fitting);
     }
 
-    let total_generators = generators.len();
+    if outcome.results.len() > 1 {
+        print_top(&outcome);
+    }
 
-    let generators: Vec<ShieldGenerator> = generators
-        .into_iter()
-        .filter(|shield| shield.class == shield_class)
-        .filter(|shield| !(test.disable_prismatic && shield.kind == "Prismatic"))
-        .collect();
+    if !outcome.cell_banks.is_empty() {
+        print_lump_sum_comparison(test, &db, &outcome)?;
+    }
 
-    // Filter the booster list using Jamie van den Berge's algorithm:
-    //
-    // Take each pair of booster, plot their values in a kdtree (effectively
-    // modelling a 4-dimensional booster-space), and use that to find pairs which
-    // will always be beaten by other pairs on all dimensions.
-    //
-    // The combinations algorithm then only returns results to test which consist
-    // of these pairs.
-    let pairs: Vec<Vec<&ShieldBooster>> =
-        boosters.iter().combinations_with_replacement(2).collect();
-    let total_pairs = pairs.len();
-
-    let pair_metrics: Vec<_> = pairs
-        .iter()
-        .enumerate()
-        .map(|(id, pair)| {
-            let exp_res = pair[0].exp_res_bonus * pair[1].exp_res_bonus;
-            let kin_res = pair[0].kin_res_bonus * pair[1].kin_res_bonus;
-            let therm_res = pair[0].therm_res_bonus * pair[1].therm_res_bonus;
-            let shield_strength_bonus =
-                pair[0].shield_strength_bonus + pair[1].shield_strength_bonus;
-            vec![
-                -exp_res,
-                -therm_res,
-                -kin_res,
-                shield_strength_bonus,
-                id as f64,
-            ]
-        })
-        .collect();
+    if !simulations.is_empty() {
+        print_simulations(&config.simulation, &simulations);
+    }
 
-    let mut tmp_metrics = pair_metrics.clone();
-    let tree = kdtree::KDTreeNode::from_points(&mut tmp_metrics[..]).unwrap();
+    Ok(())
+}
 
-    let filtered_pairs: Vec<(ShieldBooster, ShieldBooster)> = pairs
-        .into_iter()
-        .zip(pair_metrics.iter())
-        .filter(|(_, item)| test.disable_filter || !tree.dominates(&item[..]))
-        .map(|(p, _)| (p[0].clone(), p[1].clone()))
-        .collect();
+/// Explain errors in the scenario and exit, or pass on anything else.
+fn search_error(db: &Database, e: elite_shield_tester::Error) -> Box<dyn Error> {
+    match e {
+        elite_shield_tester::Error::UnknownShip(ship) => {
+            println!("Unknown ship: {}", ship);
+            println!("Known Ships:");
+            for ship in db.ships() {
+                println!(" * {}", ship);
+            }
+            std::process::exit(1);
+        }
+        elite_shield_tester::Error::InvalidShieldClass { class, min, max } => {
+            println!("Invalid shield class: {}", class);
+            println!("Valid classes: {} - {}", min, max);
+            std::process::exit(1);
+        }
+        e @ elite_shield_tester::Error::InvalidBoosterRating(_)
+        | e @ elite_shield_tester::Error::UnknownCellBank(_)
+        | e @ elite_shield_tester::Error::UnknownBulkhead(_)
+        | e @ elite_shield_tester::Error::UnknownHullReinforcement(_)
+        | e @ elite_shield_tester::Error::HullTooHeavy(_)
+        | e @ elite_shield_tester::Error::InvalidGrade { .. }
+        | e @ elite_shield_tester::Error::UnknownBlueprint(_)
+        | e @ elite_shield_tester::Error::InsufficientPower { .. }
+        | e @ elite_shield_tester::Error::Infeasible(_)
+        | e @ elite_shield_tester::Error::TooManyPinnedBoosters { .. }
+        | e @ elite_shield_tester::Error::TooManyBoosters(_)
+        | e @ elite_shield_tester::Error::NoCandidates(_)
+        | e @ elite_shield_tester::Error::InvalidSweep(_)
+        | e @ elite_shield_tester::Error::InvalidSimulation(_)
+        | e @ elite_shield_tester::Error::UnknownShield(_)
+        | e @ elite_shield_tester::Error::UnknownBooster(_)
+        | e @ elite_shield_tester::Error::TooManyUtilities { .. } => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+        e => e.into(),
+    }
+}
 
+fn print_search_setup(outcome: &Outcome) {
     println!();
     println!("---- SEARCH SETUP ----");
     println!(
         "{:>23}: {} of {}",
-        "Candidate Shields",
-        generators.len(),
-        total_generators
+        "Candidate Shields", outcome.candidate_shields, outcome.total_shields
     );
     println!(
         "{:>23}: {} of {}",
-        "Candidate Boosters",
-        boosters.len(),
-        total_boosters
+        "Candidate Boosters", outcome.candidate_boosters, outcome.total_boosters
     );
     println!(
         "{:>23}: {} of {}",
-        "Candidate Booster Pairs",
-        filtered_pairs.len(),
-        total_pairs
+        "Candidate Booster Pairs", outcome.candidate_pairs, outcome.total_pairs
     );
+    println!("{:>23}: {:.2?}", "Combinations", outcome.combinations);
+    println!("{:>23}: {:.2?}", "Search Time", outcome.search_time);
+}
 
-    let mut best_result: Option<TestResult> = None;
-
-    let mut loadouts = 0;
-    let start = std::time::Instant::now();
-
-    combinations::unique_selections_from_pairs(
-        &boosters[..],
-        &filtered_pairs[..],
-        test.shield_booster_count.min(8),
-        0,
-        |booster_loadout| {
-            let booster_stat = calculate_booster_stats(&booster_loadout[..]);
-            for shield in generators.iter() {
-                loadouts += 1;
-                let mut stats = calculate_loadout_stats(&shield, &booster_stat);
-                // These increase regen time (according to coriolis), and do not stack with boosters
-                stats.hit_points += test.reinforced_mj;
-
-                if test
-                    .regen_time_limit
-                    .map(|limit| calculate_regen_time(&stats) > limit)
-                    .unwrap_or(false)
-                {
-                    continue;
-                }
-
-                let actual_dps = calculate_actual_dps(&test, &stats);
-                let survival_time = (stats.hit_points + test.shield_cell_mj) / actual_dps;
-
-                let better = best_result
-                    .as_ref()
-                    .map(|r| {
-                        if actual_dps < 0.0 {
-                            // Regen exceeds effective DPS, favour better regen,
-                            // followed by the highest hitpoints.
-                            actual_dps < r.actual_dps
-                                || ((actual_dps - r.actual_dps).abs() < std::f64::EPSILON
-                                    && stats.hit_points > r.stats.hit_points)
-                        } else {
-                            r.actual_dps > 0.0 && survival_time > r.survival_time
-                        }
-                    })
-                    .unwrap_or(true);
-
-                if better {
-                    best_result = Some(TestResult {
-                        actual_dps,
-                        survival_time,
-                        shield: shield.clone(),
-                        boosters: booster_loadout.iter().cloned().cloned().collect(),
-                        stats,
-                    });
-                }
-            }
-        },
-    );
-
-    println!("{:>23}: {:.2?}", "Combinations", loadouts);
-    println!("{:>23}: {:.2?}", "Search Time", start.elapsed());
-
+fn print_test_setup(
+    test: &Scenario,
+    weapons: &[Weapon],
+    loadout: Option<&Loadout>,
+    outcome: &Outcome,
+) {
     println!();
     println!("---- TEST SETUP ----");
     println!();
     println!("{:>21}: {}", "Ship Type", test.ship);
-    println!("{:>21}: {}", "Shield Class", shield_class);
-    println!("{:>21}: {}", "Shield Boosters", test.shield_booster_count);
-    println!("{:>21}: {}", "Shield Booster Rating", test.shield_booster_rating.to_ascii_uppercase());
+    if let Some(loadout) = loadout {
+        let shield = &loadout.generator;
+        println!(
+            "{:>21}: {} - {} - {}",
+            "Current Shield",
+            shield.kind,
+            format_blueprint(&shield.engineering, shield.grade),
+            shield.experimental
+        );
+        for (i, booster) in loadout.boosters.iter().enumerate() {
+            println!(
+                "{:>19} {}: {} {} - {}",
+                "Booster",
+                i + 1,
+                booster.rating,
+                format_blueprint(&booster.engineering, booster.grade),
+                booster.experimental
+            );
+        }
+    }
+    if let Some(mass) = test.hull_mass {
+        println!("{:>21}: {:.1} t", "Hull Mass", mass);
+    }
+    println!("{:>21}: {}", "Shield Class", outcome.shield_class);
+    println!("{:>21}: {}", "Shield Boosters", test.booster_count());
+    if test.booster_ratings.is_empty() {
+        println!(
+            "{:>21}: {}",
+            "Shield Booster Rating",
+            test.shield_booster_rating.to_ascii_uppercase()
+        );
+    } else {
+        println!(
+            "{:>21}: {}",
+            "Booster Ratings",
+            test.booster_rating_limits()
+                .iter()
+                .map(|(rating, count)| format!("{}x {}", count, rating))
+                .join(", ")
+        );
+    }
+    if test.booster_grade != 5 || test.booster_roll < 1.0 {
+        println!(
+            "{:>21}: {}",
+            "Booster Grade",
+            Grade {
+                grade: test.booster_grade,
+                roll: test.booster_roll
+            }
+        );
+    }
+    if test.shield_grade != 5 || test.shield_roll < 1.0 {
+        println!(
+            "{:>21}: {}",
+            "Shield Grade",
+            Grade {
+                grade: test.shield_grade,
+                roll: test.shield_roll
+            }
+        );
+    }
     println!("{:>21}: {:.1} Mj", "Shield Cell Bank", test.shield_cell_mj);
+    for (i, bank) in outcome.cell_banks.iter().enumerate() {
+        println!(
+            "{:>19} {}: {}{} {} - {}",
+            "SCB",
+            i + 1,
+            bank.spec.class,
+            bank.spec.rating,
+            bank.spec.engineering,
+            bank.spec.experimental
+        );
+        println!(
+            "{:>21}  {} x {:.0} Mj, {:.1}s spin-up, {:.1}s duration, {:.0} heat each, {:.2} MW",
+            "",
+            bank.charges,
+            bank.charge_mj,
+            bank.spin_up,
+            bank.duration,
+            bank.heat,
+            bank.power_draw
+        );
+    }
     println!(
         "{:>21}: {:.1} Mj",
         "Guardian Shield Reinf", test.reinforced_mj
@@ -432,84 +625,705 @@ fn main() -> Result<(), Box<dyn Error>> {
         if test.disable_prismatic { "no" } else { "yes" }
     );
     println!("{:>21}: {}", "Regen Time Limit", limit);
-    println!("{:>21}: {}", "Explosive DPS", test.explosive_dps);
-    println!("{:>21}: {}", "Kinetic DPS", test.kinetic_dps);
-    println!("{:>21}: {}", "Thermal DPS", test.thermal_dps);
-    println!("{:>21}: {}", "Absolute DPS", test.absolute_dps);
+    let constraints = constraint::constraints(test);
+    if !constraints.is_empty() {
+        println!(
+            "{:>21}: {}",
+            "Constraints",
+            constraints.iter().map(|c| c.to_string()).join(", ")
+        );
+    }
+    if let Some(ref inventory) = test.inventory {
+        let count = |boosters: bool| {
+            inventory
+                .modules
+                .iter()
+                .filter(|m| m.is_booster() == boosters)
+                .map(|m| m.count)
+                .sum::<usize>()
+        };
+        println!(
+            "{:>21}: {} shield generators, {} boosters",
+            "Inventory",
+            count(false),
+            count(true)
+        );
+    }
+    match test.objective {
+        Objective::Survival => (),
+        Objective::Remaining => println!(
+            "{:>21}: {} after {:.0}s",
+            "Objective", test.objective, test.objective_time
+        ),
+        objective => println!("{:>21}: {}", "Objective", objective),
+    }
+    if let Some(budget) = test.power_budget() {
+        println!(
+            "{:>21}: {:.2} MW ({:.2} MW less {:.2} MW used)",
+            "Power Budget",
+            budget,
+            test.power_available.unwrap_or_default(),
+            test.power_used
+        );
+    }
+    for (i, weapon) in weapons.iter().enumerate() {
+        println!(
+            "{:>19} {}: {}x C{} {} {} - {} - {}",
+            "Weapon",
+            i + 1,
+            weapon.spec.count,
+            weapon.spec.class,
+            weapon.spec.mount,
+            weapon.spec.weapon,
+            weapon.spec.engineering,
+            weapon.spec.experimental
+        );
+        println!(
+            "{:>21}  {:.1} DPS, {:.1} sustained, {:.2} MW/s distributor",
+            "", weapon.dps, weapon.sustained_dps, weapon.distributor_draw
+        );
+    }
+    println!("{:>21}: {:.1}", "Explosive DPS", test.explosive_dps);
+    println!("{:>21}: {:.1}", "Kinetic DPS", test.kinetic_dps);
+    println!("{:>21}: {:.1}", "Thermal DPS", test.thermal_dps);
+    println!("{:>21}: {:.1}", "Absolute DPS", test.absolute_dps);
+    if test.caustic_dps > 0.0 {
+        println!("{:>21}: {:.1}", "Caustic DPS", test.caustic_dps);
+    }
     println!(
         "{:>21}: {:.1}%",
         "Damage Effectiveness",
         test.damage_effectiveness * 100.0
     );
+    let effectiveness = test.effectiveness();
+    if effectiveness
+        .iter()
+        .any(|&e| e != test.damage_effectiveness)
+    {
+        for (name, eff) in ["Explosive", "Kinetic", "Thermal", "Absolute", "Caustic"]
+            .iter()
+            .zip(effectiveness.iter())
+        {
+            println!("{:>21}: {:.1}%", format!("{} Hits", name), eff * 100.0);
+        }
+        println!(
+            "{:>21}: {:.1}%",
+            "Time Under Fire",
+            test.time_under_fire() * 100.0
+        );
+    }
+    if let Some(ref name) = test.thargoid {
+        println!("{:>21}: {}", "Thargoid", name);
+    }
+    if let Some(interval) = test.shutdown_interval {
+        println!(
+            "{:>21}: {:.0}s every {:.0}s{}",
+            "Shutdown Field",
+            test.shutdown_duration(),
+            interval,
+            if test.shutdown_neutraliser {
+                " (neutralised)"
+            } else {
+                ""
+            }
+        );
+    }
+    if let Some(ref hull) = outcome.hull {
+        println!("{:>21}: {}", "Bulkheads", hull.bulkhead);
+        for (i, hrp) in test.hull_reinforcements.iter().enumerate() {
+            println!("{:>19} {}: {}{}", "HRP", i + 1, hrp.class, hrp.rating);
+        }
+        let piercing = if let Some(piercing) = test.armour_piercing {
+            format!("{:.0}", piercing)
+        } else {
+            "full".to_owned()
+        };
+        println!("{:>21}: {}", "Armour Piercing", piercing);
+    }
+}
+
+fn print_hull(hull: &HullStat) {
     println!();
-    println!("---- TEST RESULTS ----");
+    println!("{:>20}: {:.0}", "Hull Armour", hull.armour);
+    println!("{:>20}: {:.0}", "Hull Hardness", hull.hardness);
+    println!("{:>20}: {:.2} /s", "Hull Drain Rate", hull.actual_dps);
+    println!(
+        "{:>20}: {}",
+        "Hull Survival Time",
+        format_survival_time(hull.survival_time)
+    );
+    println!(
+        "{:>20}: {:+.1}%",
+        "Hull Explosive Res",
+        (1.0 - hull.exp_res) * 100.0
+    );
+    println!(
+        "{:>20}: {:+.1}%",
+        "Hull Kinetic Res",
+        (1.0 - hull.kin_res) * 100.0
+    );
+    println!(
+        "{:>20}: {:+.1}%",
+        "Hull Thermal Res",
+        (1.0 - hull.therm_res) * 100.0
+    );
+}
+
+fn print_result(test: &Scenario, res: &TestResult) {
+    println!(
+        "{:>16}: {}",
+        "Survival Time",
+        format_survival_time(res.survival_time)
+    );
+
+    if let Some(time_to_kill) = res.time_to_kill {
+        println!(
+            "{:>16}: {}",
+            "Time To Kill",
+            format_survival_time(time_to_kill)
+        );
+    }
+
+    println!("{:>16}: {:.2} Mj/s", "Drain Rate", res.actual_dps);
+
+    if test.objective != Objective::Survival {
+        let score = objective::score(test, res.actual_dps, res.survival_time, &res.stats);
+        let score = match test.objective {
+            Objective::HitPoints | Objective::EffectiveHitPoints => format!("{:.0} Mj", score),
+            Objective::RegenTime => format!("{:.1}s from 50%", -score),
+            Objective::Remaining => format!("{:.1}%", score * 100.0),
+            _ => format!("{:.2}", score),
+        };
+        println!("{:>16}: {}", "Objective", score);
+    }
+
+    println!(
+        "{:>16}: {} - {} - {}",
+        "Shield Generator",
+        res.shield.kind,
+        format_blueprint(&res.shield.engineering, res.shield.grade),
+        res.shield.experimental
+    );
+
+    let mixed = mixed_ratings(&res.boosters);
+    let mut s = "Shield Booster".to_owned();
+    for (i, booster) in res.boosters.iter().enumerate() {
+        println!(
+            "{:>14} {}: {} - {}",
+            s,
+            i + 1,
+            format_booster(booster, mixed),
+            booster.experimental
+        );
+        s.clear();
+    }
+
     println!();
+    println!("{:>20}: {:.0} Mj", "Shield Hitpoints", res.stats.hit_points);
+    println!(
+        "{:>20}: {:.1} Mj/s ({:.1}s from 50%)",
+        "Shield Regen Rate",
+        res.stats.regen_rate,
+        calculate_regen_time(&res.stats)
+    );
+    println!(
+        "{:>20}: {:+.1}% ({:.0} Mj)",
+        "Explosive Resistance",
+        (1.0 - res.stats.exp_res) * 100.0,
+        res.stats.hit_points / res.stats.exp_res
+    );
+    println!(
+        "{:>20}: {:+.1}% ({:.0} Mj)",
+        "Kinetic Resistance",
+        (1.0 - res.stats.kin_res) * 100.0,
+        res.stats.hit_points / res.stats.kin_res
+    );
+    println!(
+        "{:>20}: {:+.1}% ({:.0} Mj)",
+        "Thermal Resistance",
+        (1.0 - res.stats.therm_res) * 100.0,
+        res.stats.hit_points / res.stats.therm_res
+    );
+    // Evaluated loadouts aren't held to the budget
+    match test.power_budget() {
+        Some(budget) if res.stats.power_draw > budget => println!(
+            "{:>20}: {:.2} MW ({:.2} MW over budget)",
+            "Power Draw",
+            res.stats.power_draw,
+            res.stats.power_draw - budget
+        ),
+        Some(budget) => println!(
+            "{:>20}: {:.2} MW ({:.2} MW spare)",
+            "Power Draw",
+            res.stats.power_draw,
+            budget - res.stats.power_draw
+        ),
+        None => println!("{:>20}: {:.2} MW", "Power Draw", res.stats.power_draw),
+    }
+}
 
-    match best_result {
+/// Show how far an evaluated loadout falls short of the best one found.
+fn print_comparison(res: &TestResult, outcome: &Outcome) {
+    println!();
+    println!("---- COMPARED TO OPTIMUM ----");
+    println!();
+
+    let best = match outcome.best() {
+        Some(best) => best,
         None => {
             println!("Nothing useful to report.");
+            return;
         }
-        Some(res) => {
-            println!(
-                "{:>16}: {}",
-                "Survival Time",
-                if res.survival_time < 0.0 {
-                    "∞".to_string()
-                } else {
-                    format!("{:.1} s", res.survival_time)
-                }
-            );
+    };
 
-            println!("{:>16}: {:.2} Mj/s", "Drain Rate", res.actual_dps);
+    // Negative survival times mean the shield never falls
+    let shortfall = match (res.survival_time < 0.0, best.survival_time < 0.0) {
+        (true, _) => None,
+        (false, true) => Some(f64::INFINITY),
+        (false, false) => Some(best.survival_time - res.survival_time),
+    };
 
+    match shortfall {
+        Some(shortfall) if shortfall > 0.05 => {
+            let gain = if shortfall.is_finite() {
+                format!(
+                    "+{:.1} s, +{:.1}%",
+                    shortfall,
+                    shortfall / res.survival_time * 100.0
+                )
+            } else {
+                "shield never falls".to_string()
+            };
             println!(
-                "{:>16}: {} - {} - {}",
-                "Shield Generator",
-                res.shield.kind,
-                res.shield.engineering,
-                res.shield.experimental
+                "{:>16}: {} / {:.2} Mj/s ({})",
+                "Optimum",
+                format_survival_time(best.survival_time),
+                best.actual_dps,
+                gain
             );
+            print_loadout(best);
+        }
+        _ => println!("No loadout does better."),
+    }
+}
+
+/// List each step from the current loadout to the optimum.
+fn print_upgrade_path(
+    test: &Scenario,
+    db: &Database,
+    current: &Loadout,
+    steps: &[UpgradeStep],
+) -> Result<(), Box<dyn Error>> {
+    println!();
+    println!("---- UPGRADE PATH ----");
+    println!();
+
+    let mut last = evaluate::evaluate(test, db, current)?;
+    println!("{:>3}: {}", "Now", format_survival_time(last.survival_time));
 
-            let mut s = "Shield Booster".to_owned();
-            for (i, booster) in res.boosters.iter().enumerate() {
+    if steps.is_empty() {
+        println!();
+        println!("This is already the optimum loadout.");
+    }
+
+    for (i, step) in steps.iter().enumerate() {
+        let gain = if step.result.survival_time < 0.0 || last.survival_time < 0.0 {
+            String::new()
+        } else {
+            format!(
+                " ({:+.1} s)",
+                step.result.survival_time - last.survival_time
+            )
+        };
+
+        println!();
+        println!(
+            "{:>3}: {}{}",
+            i + 1,
+            format_survival_time(step.result.survival_time),
+            gain
+        );
+        match step.change {
+            Change::Generator { ref from, ref to } => println!(
+                "     Shield Generator: {} - {} - {} -> {} - {} - {}",
+                from.kind,
+                format_blueprint(&from.engineering, from.grade),
+                from.experimental,
+                to.kind,
+                format_blueprint(&to.engineering, to.grade),
+                to.experimental
+            ),
+            Change::Booster {
+                slot,
+                ref from,
+                ref to,
+            } => {
+                let mixed = !test.booster_ratings.is_empty() || from.rating != to.rating;
                 println!(
-                    "{:>14} {}: {} - {}",
-                    s,
-                    i + 1,
-                    booster.engineering,
-                    booster.experimental
-                );
-                s.clear();
+                    "     Shield Booster {}: {} - {} -> {} - {}",
+                    slot + 1,
+                    format_booster(from, mixed),
+                    from.experimental,
+                    format_booster(to, mixed),
+                    to.experimental
+                )
             }
+        }
 
-            println!();
-            println!("{:>20}: {:.0} Mj", "Shield Hitpoints", res.stats.hit_points);
-            println!(
-                "{:>20}: {:.1} Mj/s ({:.1}s from 50%)",
-                "Shield Regen Rate",
-                res.stats.regen_rate,
-                calculate_regen_time(&res.stats)
-            );
-            println!(
-                "{:>20}: {:+.1}% ({:.0} Mj)",
-                "Explosive Resistance",
-                (1.0 - res.stats.exp_res) * 100.0,
-                res.stats.hit_points / res.stats.exp_res
-            );
-            println!(
-                "{:>20}: {:+.1}% ({:.0} Mj)",
-                "Kinetic Resistance",
-                (1.0 - res.stats.kin_res) * 100.0,
-                res.stats.hit_points / res.stats.kin_res
-            );
-            println!(
-                "{:>20}: {:+.1}% ({:.0} Mj)",
-                "Thermal Resistance",
-                (1.0 - res.stats.therm_res) * 100.0,
-                res.stats.hit_points / res.stats.therm_res
-            );
+        last = step.result.clone();
+    }
+
+    Ok(())
+}
+
+fn print_allocations(slots: usize, utilities: &[UtilitySpec], allocations: &[Allocation]) {
+    println!();
+    println!("---- UTILITY SLOTS ----");
+    println!();
+
+    let required: Vec<&str> = utilities
+        .iter()
+        .filter(|u| u.weight.is_none())
+        .map(|u| u.name.as_str())
+        .collect();
+    println!("{:>13}: {}", "Utility Slots", slots);
+    if !required.is_empty() {
+        println!("{:>13}: {}", "Required", required.join(", "));
+    }
+    println!();
+
+    let best = allocation::recommend(allocations).map(|a| a.boosters);
+    println!(
+        "  {:>8}  {:>10}  {:>10}  Other Utilities",
+        "Boosters", "Survival", "Marginal"
+    );
+    for allocation in allocations.iter() {
+        let survival = match allocation.result {
+            Some(ref res) => format_survival_time(res.survival_time),
+            None => "no power".to_string(),
+        };
+        let marginal = match allocation.marginal {
+            Some(m) if m.is_infinite() => "holds".to_string(),
+            Some(m) => format!("{:+.1} s", m),
+            None => String::new(),
+        };
+        println!(
+            "{} {:>8}  {:>10}  {:>10}  {}",
+            if best == Some(allocation.boosters) {
+                "*"
+            } else {
+                " "
+            },
+            allocation.boosters,
+            survival,
+            marginal,
+            allocation.utilities.iter().map(|u| &u.name).join(", ")
+        );
+    }
+
+    println!();
+    match best {
+        Some(boosters) => println!("Fit {} shield boosters.", boosters),
+        None => println!("No allocation fits the power budget and constraints."),
+    }
+}
+
+fn print_fitting(fitting: &Fitting) {
+    println!();
+    println!("---- INTERNAL SLOTS ----");
+    println!();
+
+    for (size, module) in fitting.slots.iter() {
+        let module = match module {
+            SlotModule::ShieldGenerator(class) => {
+                format!(
+                    "Class {} {} Shield Generator",
+                    class, fitting.result.shield.kind
+                )
+            }
+            SlotModule::CellBank(spec) => format!("{}{} Shield Cell Bank", spec.class, spec.rating),
+            SlotModule::GuardianReinforcement(gsrp) => format!(
+                "{}{} Guardian Shield Reinforcement ({:.0} Mj)",
+                gsrp.class, gsrp.rating, gsrp.shield_reinforcement
+            ),
+            SlotModule::HullReinforcement(spec) => {
+                format!("{}{} Hull Reinforcement Package", spec.class, spec.rating)
+            }
+            SlotModule::Empty => "empty".to_string(),
+        };
+        println!("{:>8}: {}", format!("Size {}", size), module);
+    }
+}
+
+/// Do the boosters use more than one rating?
+fn mixed_ratings(boosters: &[ShieldBooster]) -> bool {
+    boosters.iter().map(|b| b.rating).unique().count() > 1
+}
+
+/// A booster's blueprint, prefixed with its rating if ratings are mixed.
+fn format_booster(booster: &ShieldBooster, mixed: bool) -> String {
+    let name = format_blueprint(&booster.engineering, booster.grade);
+    if mixed {
+        format!("{} {}", booster.rating, name)
+    } else {
+        name
+    }
+}
+
+/// A blueprint name, with its grade unless it's a maxed grade 5.
+fn format_blueprint(name: &str, grade: Grade) -> String {
+    if grade.is_max() {
+        name.to_string()
+    } else {
+        format!("{} {}", name, grade)
+    }
+}
+
+/// Print the shield and a count of each booster, indented under a summary line.
+fn print_loadout(res: &TestResult) {
+    println!(
+        "     {} - {} - {}",
+        res.shield.kind,
+        format_blueprint(&res.shield.engineering, res.shield.grade),
+        res.shield.experimental
+    );
+    let mixed = mixed_ratings(&res.boosters);
+    for (booster, group) in &res.boosters.iter().group_by(|b| *b) {
+        println!(
+            "     {}x {} - {}",
+            group.count(),
+            format_booster(booster, mixed),
+            booster.experimental
+        );
+    }
+}
+
+fn print_top(outcome: &Outcome) {
+    let best = &outcome.results[0];
+
+    println!();
+    println!("---- TOP {} LOADOUTS ----", outcome.results.len());
+
+    for (i, res) in outcome.results.iter().enumerate() {
+        println!();
+        println!(
+            "{:>3}: {} / {:.2} Mj/s ({})",
+            i + 1,
+            format_survival_time(res.survival_time),
+            res.actual_dps,
+            format_gap(best, res)
+        );
+        print_loadout(res);
+    }
+}
+
+fn print_pareto(test: &Scenario, outcome: &Outcome) {
+    println!();
+    println!("---- PARETO FRONTIER ----");
+    println!();
+    println!("{:>21}: {}", "Ship Type", test.ship);
+    println!("{:>21}: {}", "Shield Class", outcome.shield_class);
+    println!("{:>21}: {}", "Shield Boosters", test.booster_count());
+    println!("{:>21}: {}", "Loadouts", outcome.results.len());
+
+    for (i, res) in outcome.results.iter().enumerate() {
+        println!();
+        println!(
+            "{:>3}: {:.0} Mj, {:.1} Mj/s regen, {:+.1}% exp / {:+.1}% kin / {:+.1}% therm",
+            i + 1,
+            res.stats.hit_points,
+            res.stats.regen_rate,
+            (1.0 - res.stats.exp_res) * 100.0,
+            (1.0 - res.stats.kin_res) * 100.0,
+            (1.0 - res.stats.therm_res) * 100.0
+        );
+        print_loadout(res);
+    }
+}
+
+/// Tabulate the best loadout for each damage mix, then list the loadouts by
+/// how many mixes they win.
+fn print_sweep(test: &Scenario, sweep: &Sweep) {
+    println!();
+    println!("---- DAMAGE SWEEP ----");
+    println!();
+    println!("{:>21}: {}", "Ship Type", test.ship);
+    println!("{:>21}: {}", "Shield Boosters", test.booster_count());
+    println!("{:>21}: {:.1}", "Total DPS", sweep.total_dps);
+    println!(
+        "{:>21}: {:.1}%",
+        "Damage Effectiveness",
+        test.damage_effectiveness * 100.0
+    );
+    println!("{:>21}: {}", "Damage Mixes", sweep.points.len());
+    println!("{:>21}: {:.2?}", "Search Time", sweep.search_time);
+
+    // Number each distinct loadout, most wins first
+    let key = |res: &TestResult| (sweep::shield_name(res), sweep::booster_names(res));
+    let loadouts: Vec<(&TestResult, usize)> = sweep
+        .points
+        .iter()
+        .filter_map(|p| p.result.as_ref())
+        .sorted_by_key(|&res| key(res))
+        .group_by(|&res| key(res))
+        .into_iter()
+        .map(|(_, group)| {
+            let group: Vec<&TestResult> = group.collect();
+            (group[0], group.len())
+        })
+        .sorted_by_key(|&(_, wins)| std::cmp::Reverse(wins))
+        .collect();
+    let number = |res: &TestResult| {
+        loadouts
+            .iter()
+            .position(|&(other, _)| key(other) == key(res))
+            .map(|i| i + 1)
+            .unwrap_or_default()
+    };
+
+    println!();
+    println!(
+        "{:>6} {:>6} {:>6} {:>6} {:>10}  Loadout",
+        "Exp", "Kin", "Therm", "Abs", "Survival"
+    );
+    for point in sweep.points.iter() {
+        let result = match point.result {
+            Some(ref res) => format!(
+                "{:>10}  {}",
+                format_survival_time(res.survival_time),
+                number(res)
+            ),
+            None => format!("{:>10}  -", "none"),
+        };
+        println!(
+            "{:>5.0}% {:>5.0}% {:>5.0}% {:>5.0}% {}",
+            point.explosive * 100.0,
+            point.kinetic * 100.0,
+            point.thermal * 100.0,
+            point.absolute * 100.0,
+            result
+        );
+    }
+
+    println!();
+    println!("---- SWEEP LOADOUTS ----");
+    for (i, &(res, wins)) in loadouts.iter().enumerate() {
+        println!();
+        println!(
+            "{:>3}: best against {} of {} mixes",
+            i + 1,
+            wins,
+            sweep.points.len()
+        );
+        print_loadout(res);
+    }
+}
+
+/// Show what would have been recommended had the cell banks been treated as
+/// a simple lump sum of Mj.
+fn print_lump_sum_comparison(
+    test: &Scenario,
+    db: &Database,
+    outcome: &Outcome,
+) -> Result<(), Box<dyn Error>> {
+    let lump_sum = outcome.cell_banks.iter().map(|b| b.total_mj()).sum::<f64>();
+    let lump_test = Scenario {
+        shield_cell_mj: test.shield_cell_mj + lump_sum,
+        cell_banks: vec![],
+        top: 1,
+        ..test.clone()
+    };
+    let lump_outcome = optimise(&lump_test, db)?;
+
+    println!();
+    println!("---- SCB LUMP SUM COMPARISON ----");
+    println!();
+    println!("{:>16}: {:.0} Mj", "Lump Sum", lump_sum);
+
+    match (outcome.best(), lump_outcome.best()) {
+        (Some(real), Some(lump)) => {
+            if real.shield.kind == lump.shield.kind
+                && real.shield.engineering == lump.shield.engineering
+                && real.shield.experimental == lump.shield.experimental
+                && real.boosters == lump.boosters
+            {
+                println!("Treating cells as a lump sum recommends the same loadout.");
+            } else {
+                println!("Treating cells as a lump sum would instead recommend:");
+                println!();
+                println!(
+                    "     {} (really {})",
+                    format_survival_time(lump.survival_time),
+                    format_survival_time(
+                        (lump.stats.hit_points
+                            + calculate_cell_mj(test, &outcome.cell_banks, &lump.stats))
+                            / lump.actual_dps
+                    )
+                );
+                print_loadout(lump);
+            }
         }
+        _ => println!("Nothing useful to report."),
     }
 
     Ok(())
 }
+
+fn print_simulations(sim: &Simulation, simulations: &[SimulationResult]) {
+    println!();
+    println!("---- SIMULATION ----");
+    println!();
+    println!("{:>21}: {:.1} s", "Duration", sim.duration);
+    println!("{:>21}: {:.1} s", "Regen Delay", sim.regen_delay);
+    println!("{:>21}: {:.1} s", "Fire Cycle", sim.fire_cycle);
+    println!("{:>21}: {}", "Lump Sum SCB Charges", sim.scb_charges);
+    println!();
+
+    for (i, res) in simulations.iter().enumerate() {
+        println!(
+            "{:>3}: first collapse {}, {} collapses, {:.1} s down, {} SCB charges used ({:.0} heat)",
+            i + 1,
+            res.first_collapse
+                .map(|t| format!("at {:.1} s", t))
+                .unwrap_or_else(|| "never".to_string()),
+            res.collapses,
+            res.time_broken,
+            res.scb_charges_used,
+            res.scb_heat
+        );
+    }
+
+    println!();
+    println!("{:>8}  {:>8}", "Time", "Shield");
+    for point in simulations[0].trace.iter() {
+        println!(
+            "{:>6.1} s  {:>5.0} Mj{}",
+            point.time,
+            point.hit_points,
+            if point.broken { " (down)" } else { "" }
+        );
+    }
+}
+
+fn format_survival_time(survival_time: f64) -> String {
+    if survival_time < 0.0 {
+        "∞".to_string()
+    } else {
+        format!("{:.1} s", survival_time)
+    }
+}
+
+/// Describe how far a result falls behind the winner.
+fn format_gap(best: &TestResult, res: &TestResult) -> String {
+    if best.actual_dps < 0.0 {
+        // Neither will ever fall, so compare how quickly they recover
+        format!("{:+.2} Mj/s", res.actual_dps - best.actual_dps)
+    } else if res.survival_time == best.survival_time {
+        "best".to_string()
+    } else {
+        format!(
+            "{:+.1}%",
+            (res.survival_time - best.survival_time) / best.survival_time * 100.0
+        )
+    }
+}
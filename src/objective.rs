@@ -0,0 +1,278 @@
+/*
+Optimisation objectives.
+
+What makes one loadout better than another depends on the role.  By default
+the search maximises survival time, or where regen outpaces the attacker,
+minimises drain and then maximises hit points.  The other objectives score
+each loadout, higher being better, falling back to survival time on a tie:
+
+  hit-points            raw shield hit points
+  effective-hit-points  hit points against the attacker's damage mix, after
+                        resistances
+  regen-time            the fastest regen from 50%, scored as minus the seconds
+  remaining             the share of the shield left after --objective-time
+                        seconds of the fight, including Shield Cell Banks
+  weighted              a sum of the above, weighted by --objective-weights
+
+Weights are given as `<metric>=<weight>` pairs, for example
+`survival=1,hp=0.01,regen=2`, with the metrics survival, hp, ehp, regen and
+remaining.  Weights can't be negative, so the regen weight penalises slow
+regen.  A loadout which never falls has an infinite survival time.
+
+Regen time rewards fewer hit points, so under it, or a weighted score with a
+regen weight, boosters and booster pairs aren't filtered out ahead of the
+search, which makes it slower.
+*/
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::{calculate_regen_time, calculate_shield_dps, Error, LoadoutStat, Scenario};
+
+/// What the search maximises.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Objective {
+    Survival,
+    HitPoints,
+    EffectiveHitPoints,
+    RegenTime,
+    Remaining,
+    Weighted,
+}
+
+impl FromStr for Objective {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "survival" => Ok(Objective::Survival),
+            "hit-points" | "hp" => Ok(Objective::HitPoints),
+            "effective-hit-points" | "ehp" => Ok(Objective::EffectiveHitPoints),
+            "regen-time" | "regen" => Ok(Objective::RegenTime),
+            "remaining" => Ok(Objective::Remaining),
+            "weighted" => Ok(Objective::Weighted),
+            _ => Err(Error::InvalidObjective(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Objective::Survival => "survival time",
+            Objective::HitPoints => "hit points",
+            Objective::EffectiveHitPoints => "effective hit points",
+            Objective::RegenTime => "regen time",
+            Objective::Remaining => "remaining shield",
+            Objective::Weighted => "weighted score",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Weights for the weighted objective, written as `<metric>=<weight>` pairs,
+/// for example `survival=1,ehp=0.01`.  Metrics not given weigh nothing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Weights {
+    pub survival: f64,
+    pub hit_points: f64,
+    pub effective_hit_points: f64,
+    pub regen_time: f64,
+    pub remaining: f64,
+}
+
+impl FromStr for Weights {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::InvalidObjective(s.to_string());
+        let mut weights = Weights::default();
+
+        for part in s.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let mut pair = part.splitn(2, '=').map(str::trim);
+            let metric = pair.next().ok_or_else(err)?;
+            let weight: f64 = pair.next().ok_or_else(err)?.parse().map_err(|_| err())?;
+            if weight < 0.0 || weight.is_nan() {
+                return Err(err());
+            }
+
+            let slot = match metric.to_ascii_lowercase().as_str() {
+                "survival" => &mut weights.survival,
+                "hp" => &mut weights.hit_points,
+                "ehp" => &mut weights.effective_hit_points,
+                "regen" => &mut weights.regen_time,
+                "remaining" => &mut weights.remaining,
+                _ => return Err(err()),
+            };
+            *slot = weight;
+        }
+
+        Ok(weights)
+    }
+}
+
+/// Hit points against the attacker's damage mix, after resistances.
+pub fn effective_hit_points(scenario: &Scenario, stats: &LoadoutStat) -> f64 {
+    let raw = calculate_shield_dps(
+        scenario,
+        &LoadoutStat {
+            exp_res: 1.0,
+            kin_res: 1.0,
+            therm_res: 1.0,
+            caus_res: 1.0,
+            ..stats.clone()
+        },
+    );
+    let taken = calculate_shield_dps(scenario, stats);
+
+    if taken > 0.0 {
+        stats.hit_points * raw / taken
+    } else {
+        stats.hit_points
+    }
+}
+
+/// Share of the shield left after the scenario's objective time, 0-1.
+pub fn remaining(
+    scenario: &Scenario,
+    actual_dps: f64,
+    survival_time: f64,
+    stats: &LoadoutStat,
+) -> f64 {
+    if actual_dps <= 0.0 {
+        return 1.0;
+    }
+
+    // Survival time covers the hit points and Shield Cell Banks together
+    let left = actual_dps * (survival_time - scenario.objective_time);
+    (left / stats.hit_points).clamp(0.0, 1.0)
+}
+
+/// The part of the objective which depends neither on drain rate nor on Shield
+/// Cell Banks, or None if there's no such part.
+///
+/// Everything else the objectives score only improves with more hit points and
+/// less drain, so a loadout beaten on all three can be passed over.
+pub fn static_score(scenario: &Scenario, stats: &LoadoutStat) -> Option<f64> {
+    let weights = &scenario.objective_weights;
+    match scenario.objective {
+        Objective::EffectiveHitPoints => Some(effective_hit_points(scenario, stats)),
+        Objective::RegenTime => Some(-calculate_regen_time(stats)),
+        Objective::Weighted if has_static_score(scenario) => {
+            let mut score = 0.0;
+            if weights.effective_hit_points > 0.0 {
+                score += weights.effective_hit_points * effective_hit_points(scenario, stats);
+            }
+            if weights.regen_time > 0.0 {
+                score -= weights.regen_time * calculate_regen_time(stats);
+            }
+            Some(score)
+        }
+        _ => None,
+    }
+}
+
+/// Does the scenario's objective have a static score?
+pub fn has_static_score(scenario: &Scenario) -> bool {
+    let weights = &scenario.objective_weights;
+    match scenario.objective {
+        Objective::EffectiveHitPoints | Objective::RegenTime => true,
+        Objective::Weighted => weights.effective_hit_points > 0.0 || weights.regen_time > 0.0,
+        _ => false,
+    }
+}
+
+/// Can more hit points ever make a loadout worse?
+pub fn penalises_hit_points(scenario: &Scenario) -> bool {
+    match scenario.objective {
+        Objective::RegenTime => true,
+        Objective::Weighted => scenario.objective_weights.regen_time > 0.0,
+        _ => false,
+    }
+}
+
+/// A loadout's score under the scenario's objective, higher being better.
+///
+/// Survival time is infinite for a loadout which never falls.
+pub fn score(scenario: &Scenario, actual_dps: f64, survival_time: f64, stats: &LoadoutStat) -> f64 {
+    let survival = if actual_dps > 0.0 {
+        survival_time
+    } else {
+        f64::INFINITY
+    };
+
+    match scenario.objective {
+        Objective::Survival => survival,
+        Objective::HitPoints => stats.hit_points,
+        Objective::Remaining => remaining(scenario, actual_dps, survival_time, stats),
+        Objective::Weighted => {
+            let weights = &scenario.objective_weights;
+            let mut score = static_score(scenario, stats).unwrap_or(0.0);
+            // Leave out metrics which don't count, so an infinite survival
+            // time never meets a zero weight
+            if weights.survival > 0.0 {
+                score += weights.survival * survival;
+            }
+            if weights.hit_points > 0.0 {
+                score += weights.hit_points * stats.hit_points;
+            }
+            if weights.remaining > 0.0 {
+                score += weights.remaining * remaining(scenario, actual_dps, survival_time, stats);
+            }
+            score
+        }
+        Objective::EffectiveHitPoints | Objective::RegenTime => {
+            static_score(scenario, stats).unwrap_or(0.0)
+        }
+    }
+}
+
+#[test]
+fn test_objective() {
+    let db = crate::Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        shield_booster_count: 2,
+        thermal_dps: 100.0,
+        ..Scenario::default()
+    };
+    let best = |objective: Objective, weights: &str| {
+        let scenario = Scenario {
+            objective,
+            objective_weights: weights.parse().unwrap(),
+            ..scenario.clone()
+        };
+        let result = crate::optimise(&scenario, &db).unwrap().results.remove(0);
+        let score = score(
+            &scenario,
+            result.actual_dps,
+            result.survival_time,
+            &result.stats,
+        );
+        (result, score)
+    };
+
+    let (survival, _) = best(Objective::Survival, "");
+    let (hit_points, _) = best(Objective::HitPoints, "");
+    let (regen, _) = best(Objective::RegenTime, "");
+    assert!(hit_points.stats.hit_points > survival.stats.hit_points);
+    assert!(hit_points.survival_time < survival.survival_time);
+    assert!(calculate_regen_time(&regen.stats) < calculate_regen_time(&survival.stats));
+
+    // Weighting survival alone is the default objective
+    let (weighted, _) = best(Objective::Weighted, "survival=1");
+    assert!((weighted.survival_time - survival.survival_time).abs() < 1e-9);
+
+    let (_, ehp) = best(Objective::EffectiveHitPoints, "");
+    assert!(ehp >= effective_hit_points(&scenario, &survival.stats));
+
+    assert_eq!(
+        "EHP".parse::<Objective>().unwrap(),
+        Objective::EffectiveHitPoints
+    );
+    assert!("speed".parse::<Objective>().is_err());
+    assert!("survival=-1".parse::<Weights>().is_err());
+    assert!("speed=1".parse::<Weights>().is_err());
+}
@@ -0,0 +1,285 @@
+/*
+Pareto frontier of whole loadouts.
+
+Rather than scoring loadouts against an attacker, find every loadout which
+no other loadout beats on all of hit points, explosive/kinetic/thermal
+resonance and regen rate at once.
+
+This is done in two passes of the same kdtree dominance test used to filter
+booster pairs: first over booster combinations alone (with the same shield,
+a dominated booster set always makes a dominated loadout), then over the
+survivors fitted to each candidate shield.
+
+The first pass, like the booster pair filter, is skipped when the regen time
+limit, power budget or an upper bound could rule out the boosters doing the
+dominating, leaving the ones they beat as part of the front.
+*/
+
+use std::time::Instant;
+
+use crate::constraint::{self, Tally};
+use crate::{
+    booster_selections, calculate_actual_dps, calculate_booster_stats, calculate_cell_mj,
+    calculate_cell_power, calculate_loadout_stats, calculate_regen_time, filter_booster_pairs,
+    hull, kdtree, BoosterStat, Candidates, Database, Error, LoadoutStat, Outcome, Scenario,
+    ShieldBooster, TestResult,
+};
+
+fn booster_metrics(stat: &BoosterStat) -> Vec<f64> {
+    vec![
+        -stat.exp_modifier,
+        -stat.kin_modifier,
+        -stat.therm_modifier,
+        stat.hit_point_bonus,
+    ]
+}
+
+fn loadout_metrics(stat: &LoadoutStat) -> Vec<f64> {
+    vec![
+        stat.hit_points,
+        -stat.exp_res,
+        -stat.kin_res,
+        -stat.therm_res,
+        stat.regen_rate,
+    ]
+}
+
+/// Whether booster pairs and selections beaten on every metric can be dropped
+/// before the loadouts are checked.
+fn prune_boosters(scenario: &Scenario) -> bool {
+    scenario.prefilter()
+        && scenario.regen_time_limit.is_none()
+        && scenario.power_budget().is_none()
+        && constraint::constraints(scenario).iter().all(|c| c.at_least)
+}
+
+/// Every booster selection a scenario allows and its stats.
+pub(crate) struct BoosterFront<'a> {
+    pub selections: Vec<Vec<&'a ShieldBooster>>,
+    pub stats: Vec<BoosterStat>,
+    /// Ids of the selections no other beats on all resistances and hit points
+    pub front: Vec<usize>,
+}
+
+pub(crate) fn booster_front<'a>(
+    scenario: &Scenario,
+    boosters: &'a [ShieldBooster],
+    pinned: &'a [ShieldBooster],
+    allowed_pairs: &[(ShieldBooster, ShieldBooster)],
+    prune: bool,
+) -> BoosterFront<'a> {
+    let mut selections: Vec<Vec<&ShieldBooster>> = vec![];
+    let mut stats: Vec<BoosterStat> = vec![];
+
+    booster_selections(
+        scenario,
+        boosters,
+        pinned,
+        allowed_pairs,
+        |booster_loadout| {
+            stats.push(calculate_booster_stats(booster_loadout));
+            selections.push(booster_loadout.to_vec());
+        },
+    );
+
+    let front = if prune {
+        let metrics: Vec<Vec<f64>> = stats.iter().map(booster_metrics).collect();
+        kdtree::non_dominated(&metrics)
+    } else {
+        (0..stats.len()).collect()
+    };
+    BoosterFront {
+        selections,
+        stats,
+        front,
+    }
+}
+
+/// Find every non-dominated loadout for the scenario's ship and shield class.
+///
+/// The attacker is not used to choose candidates, but drain rate and survival
+/// time are still reported against it.  Results are sorted by hit points.
+pub fn pareto_front(scenario: &Scenario, db: &Database) -> Result<Outcome, Error> {
+    let cell_banks = db.resolve_cell_banks(&scenario.cell_banks)?;
+    let hull = db.resolve_hull(scenario)?;
+    let cell_power = calculate_cell_power(&cell_banks);
+    let power_budget = scenario.power_budget();
+
+    let mut candidates = Candidates::select(scenario, db, false)?;
+    candidates.limit_power(scenario, &cell_banks)?;
+    let Candidates {
+        shield_class,
+        generators,
+        total_shields,
+        boosters,
+        total_boosters,
+        pinned,
+    } = candidates;
+    let prune = prune_boosters(scenario);
+    let (filtered_pairs, total_pairs) = filter_booster_pairs(&boosters, !prune);
+
+    let start = Instant::now();
+
+    let BoosterFront {
+        selections,
+        stats,
+        front: booster_front,
+    } = booster_front(scenario, &boosters, &pinned, &filtered_pairs, prune);
+
+    let mut tally = Tally::new(scenario);
+    let mut too_slow = false;
+    let mut over_budget = false;
+    let mut loadouts = vec![];
+    for shield in generators.iter() {
+        for &id in booster_front.iter() {
+            let mut stats = calculate_loadout_stats(shield, &stats[id]);
+            stats.hit_points += scenario.reinforced_mj;
+            stats.power_draw += cell_power;
+
+            if scenario
+                .regen_time_limit
+                .map(|limit| calculate_regen_time(&stats) > limit)
+                .unwrap_or(false)
+            {
+                too_slow = true;
+                continue;
+            }
+            if power_budget
+                .map(|budget| stats.power_draw > budget)
+                .unwrap_or(false)
+            {
+                over_budget = true;
+                continue;
+            }
+            if !tally.allows(scenario, &stats) {
+                continue;
+            }
+
+            loadouts.push((shield, id, stats));
+        }
+    }
+
+    if loadouts.is_empty() {
+        if let Some(e) = tally.infeasible() {
+            return Err(e);
+        }
+        let mut unmet = vec![];
+        if let Some(limit) = scenario.regen_time_limit.filter(|_| too_slow) {
+            unmet.push(format!("the regen time limit of {}s", limit));
+        }
+        if let Some(budget) = power_budget.filter(|_| over_budget) {
+            unmet.push(format!("the power budget of {:.2} MW", budget));
+        }
+        return Err(Error::Infeasible(unmet));
+    }
+
+    let metrics: Vec<Vec<f64>> = loadouts
+        .iter()
+        .map(|(_, _, s)| loadout_metrics(s))
+        .collect();
+
+    let mut results: Vec<TestResult> = kdtree::non_dominated(&metrics)
+        .into_iter()
+        .map(|i| {
+            let (shield, id, ref stats) = loadouts[i];
+            let actual_dps = calculate_actual_dps(scenario, stats);
+            let survival_time =
+                (stats.hit_points + calculate_cell_mj(scenario, &cell_banks, stats)) / actual_dps;
+            TestResult {
+                actual_dps,
+                survival_time,
+                time_to_kill: hull
+                    .as_ref()
+                    .map(|hull| hull::calculate_time_to_kill(survival_time, hull)),
+                shield: shield.clone(),
+                boosters: selections[id].iter().cloned().cloned().collect(),
+                stats: stats.clone(),
+            }
+        })
+        .collect();
+
+    results.sort_by(|a, b| b.stats.hit_points.partial_cmp(&a.stats.hit_points).unwrap());
+
+    Ok(Outcome {
+        shield_class,
+        candidate_shields: generators.len(),
+        total_shields,
+        candidate_boosters: boosters.len(),
+        total_boosters,
+        candidate_pairs: filtered_pairs.len(),
+        total_pairs,
+        combinations: selections.len() * generators.len(),
+        cell_banks,
+        hull,
+        search_time: start.elapsed(),
+        results,
+    })
+}
+
+#[test]
+fn test_pareto_front() {
+    let db = Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        shield_booster_count: 2,
+        ..Scenario::default()
+    };
+
+    let outcome = pareto_front(&scenario, &db).unwrap();
+    assert!(!outcome.results.is_empty());
+
+    let metrics: Vec<Vec<f64>> = outcome
+        .results
+        .iter()
+        .map(|r| loadout_metrics(&r.stats))
+        .collect();
+    for a in metrics.iter() {
+        for b in metrics.iter() {
+            let better = a.iter().zip(b).any(|(a, b)| a > b);
+            let worse = a.iter().zip(b).any(|(a, b)| a < b);
+            let dominates = better && !worse;
+            assert!(!dominates, "{:?} dominates {:?}", a, b);
+        }
+    }
+
+    // Capping duplicates leaves the pair filter unsound, so it's skipped
+    let capped = Scenario {
+        max_duplicates: 1,
+        ..scenario.clone()
+    };
+    let unfiltered = Scenario {
+        disable_filter: true,
+        ..capped.clone()
+    };
+    assert_eq!(
+        pareto_front(&capped, &db).unwrap().results.len(),
+        pareto_front(&unfiltered, &db).unwrap().results.len()
+    );
+
+    // As does a regen time limit, which can rule out the boosters doing the
+    // dominating
+    let limited = Scenario {
+        shield_booster_count: 3,
+        regen_time_limit: Some(100.0),
+        ..scenario
+    };
+    let unfiltered = Scenario {
+        disable_filter: true,
+        ..limited.clone()
+    };
+    assert_eq!(
+        pareto_front(&limited, &db).unwrap().results.len(),
+        pareto_front(&unfiltered, &db).unwrap().results.len()
+    );
+
+    let impossible = Scenario {
+        regen_time_limit: Some(1.0),
+        ..limited
+    };
+    match pareto_front(&impossible, &db) {
+        Err(Error::Infeasible(unmet)) => {
+            assert_eq!(unmet, vec!["the regen time limit of 1s"])
+        }
+        _ => panic!("expected an infeasible front"),
+    }
+}
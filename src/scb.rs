@@ -0,0 +1,171 @@
+/*
+Shield Cell Banks.
+
+Base module stats come from `data/ShieldCellBanks.csv`, keyed on class and
+rating, and engineering from `data/ShieldCellBankVariants.csv`, which holds
+fractional modifiers in the same way the booster table holds bonuses.
+*/
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ShieldCellBank {
+    pub class: u8,
+    pub rating: char,
+    /// Seconds from activation until the cell starts restoring shields
+    pub spin_up: f64,
+    /// Seconds over which each charge is delivered
+    pub duration: f64,
+    /// Mj restored per second of duration
+    pub shield_reinforcement: f64,
+    /// Heat generated per activation
+    pub therm_load: f64,
+    pub clip: u32,
+    pub ammo: u32,
+    /// MW drawn while powered
+    pub power_draw: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ShieldCellBankVariant {
+    pub engineering: String,
+    pub experimental: String,
+    pub spin_up: f64,
+    pub duration: f64,
+    pub shield_reinforcement: f64,
+    pub therm_load: f64,
+    /// Additional charges
+    pub ammo: u32,
+}
+
+/// A fitted Shield Cell Bank, written as `<class><rating>[,<engineering>[,<experimental>]]`,
+/// for example `8B,Specialised,Boss Cells`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CellBankSpec {
+    pub class: u8,
+    pub rating: char,
+    pub engineering: String,
+    pub experimental: String,
+}
+
+impl FromStr for CellBankSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::UnknownCellBank(s.to_string());
+        let mut parts = s.split(',').map(str::trim);
+
+        let size = parts.next().ok_or_else(err)?;
+        let rating = size.chars().last().ok_or_else(err)?.to_ascii_uppercase();
+        let class = size[..size.len() - rating.len_utf8()]
+            .parse()
+            .map_err(|_| err())?;
+
+        let engineering = parts.next().unwrap_or("None").to_string();
+        let experimental = parts.next().unwrap_or("No Experimental Effect").to_string();
+
+        if parts.next().is_some() {
+            return Err(err());
+        }
+
+        Ok(Self {
+            class,
+            rating,
+            engineering,
+            experimental,
+        })
+    }
+}
+
+/// Effective stats of a fitted, engineered Shield Cell Bank.
+#[derive(Debug, Clone, Serialize)]
+pub struct CellBank {
+    pub spec: CellBankSpec,
+    pub spin_up: f64,
+    pub duration: f64,
+    /// Mj restored by each charge
+    pub charge_mj: f64,
+    pub charges: u32,
+    /// Heat generated per activation
+    pub heat: f64,
+    /// MW drawn while powered
+    pub power_draw: f64,
+}
+
+impl CellBank {
+    pub fn resolve(
+        spec: &CellBankSpec,
+        banks: &[ShieldCellBank],
+        variants: &[ShieldCellBankVariant],
+    ) -> Result<Self, Error> {
+        let err = || {
+            Error::UnknownCellBank(format!(
+                "{}{},{},{}",
+                spec.class, spec.rating, spec.engineering, spec.experimental
+            ))
+        };
+
+        let base = banks
+            .iter()
+            .find(|b| b.class == spec.class && b.rating == spec.rating)
+            .ok_or_else(err)?;
+        let variant = variants
+            .iter()
+            .find(|v| {
+                v.engineering.eq_ignore_ascii_case(&spec.engineering)
+                    && v.experimental.eq_ignore_ascii_case(&spec.experimental)
+            })
+            .ok_or_else(err)?;
+
+        let duration = base.duration * (1.0 + variant.duration);
+        let reinforcement = base.shield_reinforcement * (1.0 + variant.shield_reinforcement);
+
+        Ok(Self {
+            spec: CellBankSpec {
+                engineering: variant.engineering.clone(),
+                experimental: variant.experimental.clone(),
+                ..spec.clone()
+            },
+            spin_up: base.spin_up * (1.0 + variant.spin_up),
+            duration,
+            charge_mj: reinforcement * duration,
+            charges: base.clip + base.ammo + variant.ammo,
+            heat: base.therm_load * (1.0 + variant.therm_load),
+            power_draw: base.power_draw,
+        })
+    }
+
+    pub fn total_mj(&self) -> f64 {
+        self.charge_mj * self.charges as f64
+    }
+
+    /// Mj these cells can actually put into a shield of the given size.
+    ///
+    /// A charge can't restore more than the shield holds, so big cells on a
+    /// small shield waste much of their capacity.
+    pub fn usable_mj(&self, hit_points: f64) -> f64 {
+        self.charge_mj.min(hit_points) * self.charges as f64
+    }
+}
+
+#[test]
+fn test_cell_bank_spec() {
+    let spec: CellBankSpec = "8b, Specialised, Boss Cells".parse().unwrap();
+    assert_eq!(spec.class, 8);
+    assert_eq!(spec.rating, 'B');
+    assert_eq!(spec.engineering, "Specialised");
+    assert_eq!(spec.experimental, "Boss Cells");
+
+    let spec: CellBankSpec = "6A".parse().unwrap();
+    assert_eq!(spec.engineering, "None");
+    assert_eq!(spec.experimental, "No Experimental Effect");
+
+    assert!("A".parse::<CellBankSpec>().is_err());
+    assert!("6A,a,b,c".parse::<CellBankSpec>().is_err());
+}
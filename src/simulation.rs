@@ -0,0 +1,319 @@
+/*
+Time-domain combat simulation.
+
+The steady-state model treats damage and regen as continuous averages, which
+says nothing about how long a shield lasts against a burst.  This steps
+through a fight instead: the attacker lands hits for a share of each firing
+cycle matching its time under fire, regen only starts after a delay
+without taking damage, a collapsed shield must recharge to 50% before it comes
+back up, a shutdown field stops regen while it lasts, and Shield Cell Bank
+charges restore their energy at discrete moments.
+
+Fitted cell banks fire one charge at a time, each spinning up before
+delivering its Mj over its duration, or all at once when it has none.  A lump
+sum of `shield_cell_mj` is split into charges which take effect instantly.
+*/
+
+use std::collections::VecDeque;
+
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::scb::CellBank;
+use crate::{calculate_shield_dps, Error, LoadoutStat, Scenario};
+
+/// Parameters for the time-domain simulation.
+#[derive(Debug, Clone, StructOpt, Serialize)]
+pub struct Simulation {
+    /// Seconds of combat to simulate
+    #[structopt(long = "sim-duration", default_value = "300")]
+    pub duration: f64,
+    /// Simulation time step in seconds
+    #[structopt(long = "sim-step", default_value = "0.1")]
+    pub step: f64,
+    /// Seconds without taking damage before shields start to regenerate
+    #[structopt(long, default_value = "1")]
+    pub regen_delay: f64,
+    /// Seconds per attacker firing cycle, hitting for the effectiveness share of each
+    #[structopt(long, default_value = "10")]
+    pub fire_cycle: f64,
+    /// Number of charges the --shield-cell-mj lump sum is split across
+    #[structopt(long, default_value = "1")]
+    pub scb_charges: usize,
+    /// Seconds between recorded points in the shield trace
+    #[structopt(long, default_value = "5")]
+    pub trace_interval: f64,
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self {
+            duration: 300.0,
+            step: 0.1,
+            regen_delay: 1.0,
+            fire_cycle: 10.0,
+            scb_charges: 1,
+            trace_interval: 5.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TracePoint {
+    pub time: f64,
+    pub hit_points: f64,
+    pub broken: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationResult {
+    /// Time the shield first collapsed, if it ever did
+    pub first_collapse: Option<f64>,
+    pub collapses: usize,
+    /// Total seconds spent with the shield down
+    pub time_broken: f64,
+    pub scb_charges_used: usize,
+    /// Heat generated by Shield Cell Bank activations
+    pub scb_heat: f64,
+    pub trace: Vec<TracePoint>,
+}
+
+#[derive(Debug, Clone)]
+struct Charge {
+    mj: f64,
+    spin_up: f64,
+    duration: f64,
+    heat: f64,
+}
+
+/// Simulate a fight against the scenario's attacker.
+pub fn simulate(
+    test: &Scenario,
+    sim: &Simulation,
+    cell_banks: &[CellBank],
+    stats: &LoadoutStat,
+) -> Result<SimulationResult, Error> {
+    if sim.step.is_nan() || sim.step <= 0.0 {
+        return Err(Error::InvalidSimulation("the time step must be positive"));
+    }
+    if sim.fire_cycle.is_nan() || sim.fire_cycle <= 0.0 {
+        return Err(Error::InvalidSimulation(
+            "the firing cycle must be positive",
+        ));
+    }
+
+    let max_hp = stats.hit_points;
+    let shield_dps = calculate_shield_dps(test, stats);
+    let under_fire = test.time_under_fire();
+
+    let mut charges: VecDeque<Charge> = VecDeque::new();
+    if sim.scb_charges > 0 && test.shield_cell_mj > 0.0 {
+        for _ in 0..sim.scb_charges {
+            charges.push_back(Charge {
+                mj: test.shield_cell_mj / sim.scb_charges as f64,
+                spin_up: 0.0,
+                duration: 0.0,
+                heat: 0.0,
+            });
+        }
+    }
+    for bank in cell_banks {
+        for _ in 0..bank.charges {
+            charges.push_back(Charge {
+                mj: bank.charge_mj,
+                spin_up: bank.spin_up,
+                duration: bank.duration,
+                heat: bank.heat,
+            });
+        }
+    }
+
+    let steps = (sim.duration / sim.step).ceil() as usize;
+    let trace_every = ((sim.trace_interval / sim.step).round() as usize).max(1);
+
+    let mut hp = max_hp;
+    let mut broken = false;
+    let mut since_hit = f64::INFINITY;
+    // The charge being delivered, and how long it has been running
+    let mut active: Option<(Charge, f64)> = None;
+
+    let mut result = SimulationResult {
+        first_collapse: None,
+        collapses: 0,
+        time_broken: 0.0,
+        scb_charges_used: 0,
+        scb_heat: 0.0,
+        trace: vec![],
+    };
+
+    for i in 0..=steps {
+        let time = i as f64 * sim.step;
+
+        if i % trace_every == 0 {
+            result.trace.push(TracePoint {
+                time,
+                hit_points: hp,
+                broken,
+            });
+        }
+
+        if i == steps {
+            break;
+        }
+
+        let hitting = time % sim.fire_cycle < under_fire * sim.fire_cycle;
+        let shut_down = test
+            .shutdown_interval
+            .map(|interval| time % interval < test.time_shut_down() * interval)
+            .unwrap_or(false);
+
+        if hitting {
+            since_hit = 0.0;
+        } else {
+            since_hit += sim.step;
+        }
+
+        if broken {
+            // Damage goes to the hull while the shield reboots to 50%
+            result.time_broken += sim.step;
+            hp += stats.regen_rate * sim.step;
+            if hp >= max_hp / 2.0 {
+                hp = max_hp / 2.0;
+                broken = false;
+            }
+            continue;
+        }
+
+        if hitting {
+            hp -= shield_dps * sim.step;
+        } else if since_hit >= sim.regen_delay && !shut_down {
+            hp = (hp + stats.regen_rate * sim.step).min(max_hp);
+        }
+
+        if hp <= 0.0 {
+            // Any cell still running is lost with the shield
+            hp = 0.0;
+            broken = true;
+            active = None;
+            result.collapses += 1;
+            result.first_collapse.get_or_insert(time + sim.step);
+            continue;
+        }
+
+        if let Some((charge, elapsed)) = active.as_mut() {
+            *elapsed += sim.step;
+            if charge.duration <= 0.0 {
+                // Nothing to spread the charge over, so it lands once spun up
+                if *elapsed >= charge.spin_up {
+                    hp = (hp + charge.mj).min(max_hp);
+                }
+            } else if *elapsed > charge.spin_up {
+                let delivering = (*elapsed - charge.spin_up).min(sim.step);
+                hp = (hp + charge.mj / charge.duration * delivering).min(max_hp);
+            }
+            if *elapsed >= charge.spin_up + charge.duration {
+                active = None;
+            }
+        } else if charges
+            .front()
+            .map(|charge| hp + charge.mj.min(max_hp / 2.0) <= max_hp)
+            .unwrap_or(false)
+        {
+            // Fire a cell as soon as none of its charge would be wasted, or
+            // once half the shield is gone if it holds more than that
+            let charge = charges.pop_front().unwrap();
+            result.scb_charges_used += 1;
+            result.scb_heat += charge.heat;
+            if charge.spin_up + charge.duration <= 0.0 {
+                hp = (hp + charge.mj).min(max_hp);
+            } else {
+                active = Some((charge, 0.0));
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[test]
+fn test_simulate() {
+    let stats = LoadoutStat {
+        hit_points: 1000.0,
+        regen_rate: 10.0,
+        exp_res: 1.0,
+        kin_res: 0.5,
+        therm_res: 1.0,
+        caus_res: 1.0,
+        power_draw: 0.0,
+    };
+    let test = Scenario {
+        kinetic_dps: 100.0,
+        damage_effectiveness: 1.0,
+        ..Scenario::default()
+    };
+    let sim = Simulation {
+        duration: 60.0,
+        ..Simulation::default()
+    };
+
+    // 50 Mj/s with no chance to regen: down in 20 seconds, then 50s to reboot
+    let result = simulate(&test, &sim, &[], &stats).unwrap();
+    assert!((result.first_collapse.unwrap() - 20.0).abs() < 0.2);
+    assert_eq!(result.collapses, 1);
+
+    let test = Scenario {
+        shield_cell_mj: 500.0,
+        ..test
+    };
+    let result = simulate(&test, &sim, &[], &stats).unwrap();
+    assert!((result.first_collapse.unwrap() - 30.0).abs() < 0.2);
+    assert_eq!(result.scb_charges_used, 1);
+
+    // A cell holding more than the shield fires at half and refills it
+    let oversized = Scenario {
+        shield_cell_mj: 5000.0,
+        ..test.clone()
+    };
+    let result = simulate(&oversized, &sim, &[], &stats).unwrap();
+    assert!((result.first_collapse.unwrap() - 30.0).abs() < 0.2);
+    assert_eq!(result.scb_charges_used, 1);
+
+    // The same charge delivered after a spin-up, over a few seconds
+    let bank = CellBank {
+        spec: "8A".parse().unwrap(),
+        spin_up: 5.0,
+        duration: 5.0,
+        charge_mj: 500.0,
+        charges: 1,
+        heat: 800.0,
+        power_draw: 3.36,
+    };
+    let test = Scenario {
+        shield_cell_mj: 0.0,
+        ..test
+    };
+    let result = simulate(&test, &sim, std::slice::from_ref(&bank), &stats).unwrap();
+    assert!((result.first_collapse.unwrap() - 30.0).abs() < 0.3);
+    assert!((result.scb_heat - 800.0).abs() < 1e-9);
+
+    // A charge with no duration lands all at once after its spin-up
+    let instant = CellBank {
+        duration: 0.0,
+        ..bank
+    };
+    let result = simulate(&test, &sim, &[instant], &stats).unwrap();
+    assert!((result.first_collapse.unwrap() - 30.0).abs() < 0.3);
+
+    for sim in [
+        Simulation {
+            step: 0.0,
+            ..sim.clone()
+        },
+        Simulation {
+            fire_cycle: 0.0,
+            ..sim
+        },
+    ] {
+        assert!(simulate(&test, &sim, &[], &stats).is_err());
+    }
+}
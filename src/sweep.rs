@@ -0,0 +1,253 @@
+/*
+Sweeping the attacker's damage mix.
+
+A loadout tuned for one attacker can fall apart against another.  To see which
+loadouts hold up across threats, the sweep shares the scenario's total
+explosive, kinetic, thermal and absolute DPS out over a grid of mixes, and
+finds the best loadout for each.  With `steps` divisions, each type takes a
+multiple of 1/steps of the total, so 4 steps gives mixes such as 25% explosive,
+50% kinetic and 25% absolute, 35 in all.  Effectiveness and caustic damage
+stay as given.
+
+Only the DPS changes from one mix to the next, so the candidates and booster
+pairs are chosen once and searched at every point.  Boosters resisting one
+damage type are kept, since that type is dealt somewhere on the grid.
+*/
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::blueprint::Grade;
+use crate::{
+    filter_booster_pairs, search, serialize_secs, Candidates, Database, Error, Scenario, TestResult,
+};
+
+/// The best loadout against one damage mix, given as shares of the total DPS.
+#[derive(Debug, Clone, Serialize)]
+pub struct SweepPoint {
+    pub explosive: f64,
+    pub kinetic: f64,
+    pub thermal: f64,
+    pub absolute: f64,
+    /// None if no loadout meets the constraints against this mix
+    pub result: Option<TestResult>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Sweep {
+    pub total_dps: f64,
+    pub points: Vec<SweepPoint>,
+    #[serde(serialize_with = "serialize_secs")]
+    pub search_time: Duration,
+}
+
+/// Every mix of the four damage types in multiples of 1/steps.
+pub fn damage_mixes(steps: usize) -> Vec<[f64; 4]> {
+    let share = |n: usize| n as f64 / steps as f64;
+    let mut mixes = vec![];
+    for explosive in 0..=steps {
+        for kinetic in 0..=steps - explosive {
+            for thermal in 0..=steps - explosive - kinetic {
+                let absolute = steps - explosive - kinetic - thermal;
+                mixes.push([
+                    share(explosive),
+                    share(kinetic),
+                    share(thermal),
+                    share(absolute),
+                ]);
+            }
+        }
+    }
+    mixes
+}
+
+/// Find the best loadout against each mix of the scenario's total DPS.
+pub fn sweep(scenario: &Scenario, db: &Database, steps: usize) -> Result<Sweep, Error> {
+    let total_dps = scenario.explosive_dps
+        + scenario.kinetic_dps
+        + scenario.thermal_dps
+        + scenario.absolute_dps;
+    if steps == 0 {
+        return Err(Error::InvalidSweep("it needs at least one step"));
+    }
+    if total_dps <= 0.0 {
+        return Err(Error::InvalidSweep("there's no damage to share out"));
+    }
+
+    let cell_banks = db.resolve_cell_banks(&scenario.cell_banks)?;
+    let mut candidates = Candidates::select(scenario, db, false)?;
+    candidates.limit_power(scenario, &cell_banks)?;
+    let (filtered_pairs, total_pairs) =
+        filter_booster_pairs(&candidates.boosters, !scenario.prefilter());
+
+    let start = Instant::now();
+    let mut points = vec![];
+    for [explosive, kinetic, thermal, absolute] in damage_mixes(steps) {
+        let point = Scenario {
+            explosive_dps: explosive * total_dps,
+            kinetic_dps: kinetic * total_dps,
+            thermal_dps: thermal * total_dps,
+            absolute_dps: absolute * total_dps,
+            top: 1,
+            ..scenario.clone()
+        };
+
+        let result = match search(&point, db, &candidates, &filtered_pairs, total_pairs) {
+            Ok(mut outcome) if !outcome.results.is_empty() => Some(outcome.results.remove(0)),
+            Ok(_) | Err(Error::Infeasible(_)) => None,
+            Err(e) => return Err(e),
+        };
+
+        points.push(SweepPoint {
+            explosive,
+            kinetic,
+            thermal,
+            absolute,
+            result,
+        });
+    }
+
+    Ok(Sweep {
+        total_dps,
+        points,
+        search_time: start.elapsed(),
+    })
+}
+
+/// A blueprint name, with its grade unless it's a maxed grade 5.
+fn blueprint(name: &str, grade: Grade) -> String {
+    if grade.is_max() {
+        name.to_string()
+    } else {
+        format!("{} {}", name, grade)
+    }
+}
+
+/// The shield generator of a result, such as `Prismatic - Reinforced - Hi-Cap`.
+pub fn shield_name(res: &TestResult) -> String {
+    format!(
+        "{} - {} - {}",
+        res.shield.kind,
+        blueprint(&res.shield.engineering, res.shield.grade),
+        res.shield.experimental
+    )
+}
+
+/// The boosters of a result, such as `2x Heavy Duty - Super Capacitors;
+/// 1x Resistance Augmented - Thermo Block`, with ratings if they're mixed.
+pub fn booster_names(res: &TestResult) -> String {
+    let mixed = res.boosters.iter().map(|b| b.rating).unique().count() > 1;
+    res.boosters
+        .iter()
+        .group_by(|b| *b)
+        .into_iter()
+        .map(|(booster, group)| {
+            let rating = if mixed {
+                format!("{} ", booster.rating)
+            } else {
+                String::new()
+            };
+            format!(
+                "{}x {}{} - {}",
+                group.count(),
+                rating,
+                blueprint(&booster.engineering, booster.grade),
+                booster.experimental
+            )
+        })
+        .join("; ")
+}
+
+/// Write a sweep as CSV, one row per damage mix.
+pub fn write_csv<W: Write>(sweep: &Sweep, writer: W) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "Explosive",
+        "Kinetic",
+        "Thermal",
+        "Absolute",
+        "SurvivalTime",
+        "HitPoints",
+        "ShieldGenerator",
+        "Boosters",
+    ])?;
+
+    for point in sweep.points.iter() {
+        let mut record = vec![
+            point.explosive.to_string(),
+            point.kinetic.to_string(),
+            point.thermal.to_string(),
+            point.absolute.to_string(),
+        ];
+        match point.result {
+            Some(ref res) => record.extend(vec![
+                // A loadout which never falls survives forever
+                if res.actual_dps > 0.0 {
+                    format!("{:.2}", res.survival_time)
+                } else {
+                    "inf".to_string()
+                },
+                format!("{:.1}", res.stats.hit_points),
+                shield_name(res),
+                booster_names(res),
+            ]),
+            None => record.extend(vec![String::new(); 4]),
+        }
+        writer.write_record(&record)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[test]
+fn test_sweep() {
+    let db = Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        shield_booster_count: 2,
+        thermal_dps: 100.0,
+        ..Scenario::default()
+    };
+
+    assert_eq!(damage_mixes(4).len(), 35);
+    let sweep = sweep(&scenario, &db, 2).unwrap();
+    assert_eq!(sweep.points.len(), 10);
+    for point in sweep.points.iter() {
+        let total = point.explosive + point.kinetic + point.thermal + point.absolute;
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    // The same as searching for the mix on its own
+    let thermal = sweep
+        .points
+        .iter()
+        .find(|p| p.thermal == 1.0)
+        .and_then(|p| p.result.as_ref())
+        .unwrap();
+    let best = crate::optimise(&scenario, &db).unwrap().results.remove(0);
+    assert!((thermal.survival_time - best.survival_time).abs() < 1e-9);
+
+    // Different mixes want different boosters
+    let boosters: Vec<String> = sweep
+        .points
+        .iter()
+        .filter_map(|p| p.result.as_ref())
+        .map(booster_names)
+        .unique()
+        .collect();
+    assert!(boosters.len() > 1);
+
+    let mut csv = vec![];
+    write_csv(&sweep, &mut csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap().lines().count(), 11);
+
+    let no_damage = Scenario {
+        thermal_dps: 0.0,
+        ..scenario
+    };
+    assert!(crate::sweep::sweep(&no_damage, &db, 2).is_err());
+}
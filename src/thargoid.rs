@@ -0,0 +1,106 @@
+/*
+Thargoid attackers.
+
+Thargoid weapons mostly deal absolute damage, which no shield resists, and
+interceptors add caustic missiles on top.  Caustic damage gets its own
+resonance, which shield generators may set via a `CausRes` column and
+boosters leave alone.
+
+Interceptors can also fire a shutdown field, during which the shield doesn't
+regenerate at all.  Over a long fight this is modelled as the share of time
+spent shut down, which a Guardian Shutdown Neutraliser cancels.
+
+Ready-made profiles live in `data/ThargoidProfiles.csv`.
+*/
+
+use serde::{Deserialize, Serialize};
+
+use crate::Scenario;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all(deserialize = "PascalCase"))]
+pub struct ThargoidProfile {
+    pub name: String,
+    pub explosive: f64,
+    pub kinetic: f64,
+    pub thermal: f64,
+    pub absolute: f64,
+    pub caustic: f64,
+    /// Seconds between shutdown field pulses, if the attacker has one
+    pub shutdown_interval: Option<f64>,
+    pub shutdown_duration: Option<f64>,
+}
+
+impl Scenario {
+    /// This scenario with a Thargoid's damage added to the raw per-type
+    /// figures.  Its shutdown field's interval and duration apply unless
+    /// they're already given.
+    pub fn with_thargoid(&self, profile: &ThargoidProfile) -> Scenario {
+        let mut test = self.clone();
+        test.explosive_dps += profile.explosive;
+        test.kinetic_dps += profile.kinetic;
+        test.thermal_dps += profile.thermal;
+        test.absolute_dps += profile.absolute;
+        test.caustic_dps += profile.caustic;
+
+        if test.shutdown_interval.is_none() {
+            test.shutdown_interval = profile.shutdown_interval;
+        }
+        if test.shutdown_duration.is_none() {
+            test.shutdown_duration = profile.shutdown_duration;
+        }
+        test
+    }
+
+    /// Seconds each shutdown field stops shield regen, 15 unless given.
+    pub fn shutdown_duration(&self) -> f64 {
+        self.shutdown_duration.unwrap_or(15.0)
+    }
+
+    /// Share of the fight the shield spends shut down by a shutdown field.
+    pub fn time_shut_down(&self) -> f64 {
+        match self.shutdown_interval {
+            Some(interval) if !self.shutdown_neutraliser && interval > 0.0 => {
+                (self.shutdown_duration() / interval).min(1.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Share of the fight the shield is free to regenerate.
+    pub fn time_regenerating(&self) -> f64 {
+        (1.0 - self.time_under_fire()) * (1.0 - self.time_shut_down())
+    }
+}
+
+#[test]
+fn test_thargoid_profile() {
+    let db = crate::Database::builtin().unwrap();
+    let hydra = db.thargoid("hydra").unwrap();
+    let test = Scenario {
+        kinetic_dps: 10.0,
+        damage_effectiveness: 0.5,
+        ..Scenario::default()
+    }
+    .with_thargoid(hydra);
+
+    assert!((test.kinetic_dps - 10.0).abs() < 1e-9);
+    assert!(test.caustic_dps > 0.0);
+    assert!((test.time_regenerating() - 0.5 * (1.0 - 15.0 / 90.0)).abs() < 1e-9);
+
+    let test = Scenario {
+        shutdown_neutraliser: true,
+        ..test
+    };
+    assert!((test.time_regenerating() - 0.5).abs() < 1e-9);
+
+    // A duration given by the user wins over the profile's
+    let test = Scenario {
+        shutdown_duration: Some(5.0),
+        ..Scenario::default()
+    }
+    .with_thargoid(hydra);
+    assert!((test.time_shut_down() - 5.0 / 90.0).abs() < 1e-9);
+
+    assert!(db.thargoid("Glaive").is_err());
+}
@@ -0,0 +1,189 @@
+/*
+Upgrade path from a current loadout to the optimum.
+
+Engineering is done one module at a time, so it helps to know which change
+is worth making first.  Each step swaps one module of the current loadout,
+either the shield generator or a single booster, for one the optimum uses,
+choosing whichever swap gives the best result.  This repeats until the
+loadout matches the optimum.
+
+Modules match when they share a blueprint, experimental effect and grade, so
+a booster which needs a better roll still counts as a step.  Boosters are
+swapped for ones of the same rating where possible.
+*/
+
+use serde::Serialize;
+
+use crate::evaluate::evaluate;
+use crate::journal::Loadout;
+use crate::{
+    compare_results, Database, Error, Scenario, ShieldBooster, ShieldGenerator, TestResult,
+};
+
+/// A single module swap.
+#[derive(Debug, Clone, Serialize)]
+pub enum Change {
+    Generator {
+        from: ShieldGenerator,
+        to: ShieldGenerator,
+    },
+    /// Replace the booster in the given slot, counting from 0
+    Booster {
+        slot: usize,
+        from: ShieldBooster,
+        to: ShieldBooster,
+    },
+}
+
+impl Change {
+    fn apply(&self, loadout: &Loadout) -> Loadout {
+        let mut loadout = loadout.clone();
+        match self {
+            Change::Generator { to, .. } => loadout.generator = to.clone(),
+            Change::Booster { slot, to, .. } => loadout.boosters[*slot] = to.clone(),
+        }
+        loadout
+    }
+}
+
+/// A change along with the result of the loadout it leaves.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpgradeStep {
+    pub change: Change,
+    pub result: TestResult,
+}
+
+fn same_generator(a: &ShieldGenerator, b: &ShieldGenerator) -> bool {
+    a.class == b.class
+        && a.kind == b.kind
+        && a.engineering == b.engineering
+        && a.experimental == b.experimental
+        && a.grade == b.grade
+}
+
+fn same_booster(a: &ShieldBooster, b: &ShieldBooster) -> bool {
+    a.rating == b.rating
+        && a.engineering == b.engineering
+        && a.experimental == b.experimental
+        && a.grade == b.grade
+}
+
+/// Fitted booster slots the target doesn't want, and target boosters not yet fitted.
+fn unmatched<'a>(
+    fitted: &[ShieldBooster],
+    target: &'a [ShieldBooster],
+) -> (Vec<usize>, Vec<&'a ShieldBooster>) {
+    let mut wanted: Vec<&ShieldBooster> = target.iter().collect();
+    let mut spare = vec![];
+
+    for (slot, booster) in fitted.iter().enumerate() {
+        match wanted.iter().position(|b| same_booster(b, booster)) {
+            Some(i) => {
+                wanted.remove(i);
+            }
+            None => spare.push(slot),
+        }
+    }
+
+    (spare, wanted)
+}
+
+/// Order the changes which turn `current` into the `target` loadout, best first.
+pub fn upgrade_path(
+    scenario: &Scenario,
+    db: &Database,
+    current: &Loadout,
+    target: &TestResult,
+) -> Result<Vec<UpgradeStep>, Error> {
+    let mut loadout = current.clone();
+    let mut steps = vec![];
+
+    loop {
+        let mut changes = vec![];
+
+        if !same_generator(&loadout.generator, &target.shield) {
+            changes.push(Change::Generator {
+                from: loadout.generator.clone(),
+                to: target.shield.clone(),
+            });
+        }
+
+        // Keep each slot's rating where the target allows, as lower ratings
+        // are usually there to save power
+        let (spare, wanted) = unmatched(&loadout.boosters, &target.boosters);
+        for &slot in spare.iter() {
+            let rating = loadout.boosters[slot].rating;
+            let same_rating = wanted.iter().any(|b| b.rating == rating);
+            for &to in wanted.iter().filter(|b| !same_rating || b.rating == rating) {
+                changes.push(Change::Booster {
+                    slot,
+                    from: loadout.boosters[slot].clone(),
+                    to: to.clone(),
+                });
+            }
+        }
+
+        let mut best: Option<(Change, Loadout, TestResult)> = None;
+        for change in changes {
+            let next = change.apply(&loadout);
+            let result = evaluate(scenario, db, &next)?;
+            let better = best
+                .as_ref()
+                .map(|(_, _, b)| compare_results(scenario, &result, b) == std::cmp::Ordering::Less)
+                .unwrap_or(true);
+            if better {
+                best = Some((change, next, result));
+            }
+        }
+
+        match best {
+            Some((change, next, result)) => {
+                steps.push(UpgradeStep { change, result });
+                loadout = next;
+            }
+            None => return Ok(steps),
+        }
+    }
+}
+
+#[test]
+fn test_upgrade_path() {
+    let db = Database::builtin().unwrap();
+    let scenario = Scenario {
+        ship: "Python".to_string(),
+        shield_booster_count: 3,
+        kinetic_dps: 100.0,
+        thermal_dps: 50.0,
+        ..Scenario::default()
+    };
+
+    let best = crate::optimise(&scenario, &db).unwrap().results.remove(0);
+    let current = crate::evaluate::resolve_loadout(
+        &scenario,
+        &db,
+        &"Normal,Thermal resistant,Fast Charge".parse().unwrap(),
+        &[
+            "Blast Resistance".parse().unwrap(),
+            "Blast Resistance".parse().unwrap(),
+            "Heavy Duty,Super Capacitors".parse().unwrap(),
+        ],
+    )
+    .unwrap();
+
+    let steps = upgrade_path(&scenario, &db, &current, &best).unwrap();
+    assert!(!steps.is_empty() && steps.len() <= 4);
+
+    // Ends at the optimum
+    let last = &steps.last().unwrap().result;
+    assert!((last.survival_time - best.survival_time).abs() < 1e-9);
+
+    // Nothing left to do from the optimum itself
+    let optimum = Loadout {
+        ship: "Python".to_string(),
+        generator: best.shield.clone(),
+        boosters: best.boosters.clone(),
+    };
+    assert!(upgrade_path(&scenario, &db, &optimum, &best)
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,248 @@
+/*
+Attacker weapons.
+
+Rather than working out per-type DPS by hand, an attacker can be described by
+the weapons it carries.  Base stats come from `data/Weapons.csv`, keyed on
+weapon, mount and class, with the damage split across types as fractions.
+
+Engineering comes from `data/WeaponModifications.csv`, one row per blueprint
+or experimental effect, marked with which it is, holding fractional DPS and distributor draw modifiers
+like the cell bank table.  Effects which change damage type, such as
+Incendiary Rounds, also give a new damage split which replaces the weapon's
+own.  Heat-dependent effects such as Thermal Conduit use their bonus at
+typical combat heat.
+*/
+
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Scenario};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WeaponModule {
+    pub weapon: String,
+    pub mount: String,
+    pub class: u8,
+    pub explosive: f64,
+    pub kinetic: f64,
+    pub thermal: f64,
+    pub absolute: f64,
+    pub dps: f64,
+    /// DPS including reloads and ammo limits
+    pub sustained_dps: f64,
+    /// Distributor MW drawn per second of fire
+    pub distributor_draw: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WeaponModification {
+    /// `Blueprint` or `Experimental`
+    pub kind: String,
+    pub name: String,
+    pub dps: f64,
+    pub distributor_draw: f64,
+    /// Damage split replacing the weapon's own, for effects which change damage type
+    pub explosive: Option<f64>,
+    pub kinetic: Option<f64>,
+    pub thermal: Option<f64>,
+    pub absolute: Option<f64>,
+}
+
+/// Fitted weapons, written as `[<count>x ]C<class> <mount> <weapon>[,<engineering>[,<experimental>]]`,
+/// for example `2x C3 Gimballed Multi-cannon,Overcharged,Incendiary Rounds`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeaponSpec {
+    pub count: u32,
+    pub class: u8,
+    pub mount: String,
+    pub weapon: String,
+    pub engineering: String,
+    pub experimental: String,
+}
+
+impl FromStr for WeaponSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || Error::UnknownWeapon(s.to_string());
+        let mut parts = s.split(',').map(str::trim);
+
+        let mut words = parts.next().ok_or_else(err)?.split_whitespace().peekable();
+
+        let count = match words.peek() {
+            Some(word) if word.ends_with(&['x', 'X'][..]) => {
+                let count = word[..word.len() - 1].parse().map_err(|_| err())?;
+                words.next();
+                count
+            }
+            _ => 1,
+        };
+
+        let size = words.next().ok_or_else(err)?;
+        let class = size
+            .trim_start_matches(&['C', 'c'][..])
+            .parse()
+            .map_err(|_| err())?;
+        let mount = words.next().ok_or_else(err)?.to_string();
+        let weapon = words.collect::<Vec<_>>().join(" ");
+
+        if weapon.is_empty() {
+            return Err(err());
+        }
+
+        let engineering = parts.next().unwrap_or("None").to_string();
+        let experimental = parts.next().unwrap_or("No Experimental Effect").to_string();
+
+        if parts.next().is_some() {
+            return Err(err());
+        }
+
+        Ok(Self {
+            count,
+            class,
+            mount,
+            weapon,
+            engineering,
+            experimental,
+        })
+    }
+}
+
+/// Effective stats of a group of fitted, engineered weapons.
+#[derive(Debug, Clone, Serialize)]
+pub struct Weapon {
+    pub spec: WeaponSpec,
+    pub explosive: f64,
+    pub kinetic: f64,
+    pub thermal: f64,
+    pub absolute: f64,
+    /// Total DPS of every weapon in the group
+    pub dps: f64,
+    pub sustained_dps: f64,
+    pub distributor_draw: f64,
+}
+
+impl Weapon {
+    pub fn resolve(
+        spec: &WeaponSpec,
+        modules: &[WeaponModule],
+        modifications: &[WeaponModification],
+    ) -> Result<Self, Error> {
+        let err = |name: &str| Error::UnknownWeapon(name.to_string());
+
+        let base = modules
+            .iter()
+            .find(|m| {
+                m.class == spec.class
+                    && m.mount.eq_ignore_ascii_case(&spec.mount)
+                    && m.weapon.eq_ignore_ascii_case(&spec.weapon)
+            })
+            .ok_or_else(|| err(&format!("C{} {} {}", spec.class, spec.mount, spec.weapon)))?;
+        // A blueprint given as the experimental effect, or the reverse, is
+        // named but not of the kind asked for
+        let modification = |kind: &str, name: &str| {
+            let named = |m: &&WeaponModification| m.name.eq_ignore_ascii_case(name);
+            match modifications.iter().filter(named).find(|m| m.kind == kind) {
+                Some(m) => Ok(m),
+                None if modifications.iter().any(|m| named(&m)) => {
+                    Err(Error::UnknownBlueprint(name.to_string()))
+                }
+                None => Err(err(name)),
+            }
+        };
+        let engineering = modification("Blueprint", &spec.engineering)?;
+        let experimental = modification("Experimental", &spec.experimental)?;
+
+        let mut split = [base.explosive, base.kinetic, base.thermal, base.absolute];
+        for m in [engineering, experimental].iter() {
+            if let (Some(e), Some(k), Some(t), Some(a)) =
+                (m.explosive, m.kinetic, m.thermal, m.absolute)
+            {
+                split = [e, k, t, a];
+            }
+        }
+
+        let count = spec.count as f64;
+        let dps_modifier = (1.0 + engineering.dps) * (1.0 + experimental.dps);
+        let draw_modifier =
+            (1.0 + engineering.distributor_draw) * (1.0 + experimental.distributor_draw);
+
+        Ok(Self {
+            spec: WeaponSpec {
+                mount: base.mount.clone(),
+                weapon: base.weapon.clone(),
+                engineering: engineering.name.clone(),
+                experimental: experimental.name.clone(),
+                ..spec.clone()
+            },
+            explosive: split[0],
+            kinetic: split[1],
+            thermal: split[2],
+            absolute: split[3],
+            dps: base.dps * dps_modifier * count,
+            sustained_dps: base.sustained_dps * dps_modifier * count,
+            distributor_draw: base.distributor_draw * draw_modifier * count,
+        })
+    }
+}
+
+impl Scenario {
+    /// This scenario with the given weapons' DPS added to the raw per-type
+    /// figures, using sustained DPS if the scenario asks for it.
+    pub fn with_weapons(&self, weapons: &[Weapon]) -> Scenario {
+        let mut test = self.clone();
+        for weapon in weapons {
+            let dps = if self.sustained_dps {
+                weapon.sustained_dps
+            } else {
+                weapon.dps
+            };
+            test.explosive_dps += dps * weapon.explosive;
+            test.kinetic_dps += dps * weapon.kinetic;
+            test.thermal_dps += dps * weapon.thermal;
+            test.absolute_dps += dps * weapon.absolute;
+        }
+        test
+    }
+}
+
+#[test]
+fn test_weapon_spec() {
+    let spec: WeaponSpec = "2x C3 Gimballed Multi-cannon, Overcharged, Incendiary Rounds"
+        .parse()
+        .unwrap();
+    assert_eq!(spec.count, 2);
+    assert_eq!(spec.class, 3);
+    assert_eq!(spec.mount, "Gimballed");
+    assert_eq!(spec.weapon, "Multi-cannon");
+    assert_eq!(spec.experimental, "Incendiary Rounds");
+
+    let plain: WeaponSpec = "C1 Fixed Pulse Laser".parse().unwrap();
+    assert_eq!(plain.count, 1);
+    assert_eq!(plain.engineering, "None");
+
+    let db = crate::Database::builtin().unwrap();
+    let weapons = db.resolve_weapons(&[spec]).unwrap();
+    let test = Scenario {
+        kinetic_dps: 10.0,
+        ..Scenario::default()
+    }
+    .with_weapons(&weapons);
+    assert!((test.kinetic_dps - 10.0).abs() < 1e-9);
+    assert!((test.thermal_dps - 2.0 * 15.23 * 1.8 * 0.95).abs() < 1e-9);
+
+    // Blueprints and experimental effects aren't interchangeable
+    let swapped: WeaponSpec = "C3 Gimballed Multi-cannon, Incendiary Rounds, Overcharged"
+        .parse()
+        .unwrap();
+    assert!(matches!(
+        db.resolve_weapons(&[swapped]),
+        Err(Error::UnknownBlueprint(_))
+    ));
+
+    assert!("2x C3 Gimballed".parse::<WeaponSpec>().is_err());
+    assert!("C3 Fixed Cannon,a,b,c".parse::<WeaponSpec>().is_err());
+}